@@ -0,0 +1,139 @@
+// benches/ecs_iteration.rs
+// Description:
+// Compares iterating 100k entities with two components through `EntityManager::query` and through an
+// EntitySystem against the old layout, where every component was boxed in a HashMap<TypeId, HashMap<u32, Box<dyn Any>>>
+// and systems looked up and downcast every component by hand.
+// Run with `cargo bench --bench ecs_iteration`.
+
+// Doesn't need SDL, `cargo bench --no-default-features` works too.
+
+use game::ecs::component::Component;
+use game::ecs::entity_manager::{Entity, EntityManager};
+use game::ecs::storage::Mut;
+use game::ecs::system::{EntitySystem, Stage};
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+const ENTITIES: u32 = 100_000;
+const RUNS: usize = 20;
+
+struct Position {
+    x: f32,
+    y: f32,
+}
+
+struct Velocity {
+    delta_x: f32,
+    delta_y: f32,
+}
+
+impl Component for Position {}
+impl Component for Velocity {}
+
+// Runs the closure a few times and keeps the fastest run
+fn best_of(mut run: impl FnMut()) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            run();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn bench_boxed_hash_maps() -> Duration {
+    let mut components: HashMap<TypeId, HashMap<u32, Box<dyn Any>>> = HashMap::new();
+    for id in 0..ENTITIES {
+        components
+            .entry(TypeId::of::<Position>())
+            .or_default()
+            .insert(id, Box::new(Position { x: id as f32, y: 0.0 }));
+        components
+            .entry(TypeId::of::<Velocity>())
+            .or_default()
+            .insert(id, Box::new(Velocity { delta_x: 1.0, delta_y: 0.5 }));
+    }
+
+    best_of(|| {
+        // Same pattern the systems used: walk one map's keys, then look up and downcast both components
+        let ids: Vec<u32> = components[&TypeId::of::<Velocity>()].keys().copied().collect();
+        for id in ids {
+            let velocity = components[&TypeId::of::<Velocity>()][&id]
+                .downcast_ref::<Velocity>()
+                .map(|velocity| (velocity.delta_x, velocity.delta_y));
+            let position = components
+                .get_mut(&TypeId::of::<Position>())
+                .and_then(|positions| positions.get_mut(&id))
+                .and_then(|position| position.downcast_mut::<Position>());
+            if let (Some((delta_x, delta_y)), Some(position)) = (velocity, position) {
+                position.x += delta_x;
+                position.y += delta_y;
+            }
+        }
+        black_box(&components);
+    })
+}
+
+fn spawn_entities() -> EntityManager {
+    let mut entity_manager = EntityManager::new();
+    for id in 0..ENTITIES {
+        entity_manager.spawn((Position { x: id as f32, y: 0.0 }, Velocity { delta_x: 1.0, delta_y: 0.5 }));
+    }
+    entity_manager
+}
+
+fn bench_query() -> Duration {
+    let mut entity_manager = spawn_entities();
+    best_of(|| {
+        for (mut position, velocity) in entity_manager.query::<(&mut Position, &Velocity)>() {
+            position.x += velocity.delta_x;
+            position.y += velocity.delta_y;
+        }
+        black_box(&entity_manager);
+    })
+}
+
+// The same loop as an EntitySystem, run through a whole frame so scheduling and the system context are included
+struct Move;
+
+impl EntitySystem for Move {
+    type Query = (&'static mut Position, &'static Velocity);
+    type Filter = ();
+
+    fn update_entity(&mut self, _entity: Entity, (mut position, velocity): (Mut<'_, Position>, &Velocity)) {
+        position.x += velocity.delta_x;
+        position.y += velocity.delta_y;
+    }
+
+    // Runs once a frame whatever the frame time
+    fn stage(&self) -> Stage {
+        Stage::PostPhysics
+    }
+}
+
+fn bench_entity_system() -> Duration {
+    let mut entity_manager = spawn_entities();
+    entity_manager.add_system(Move);
+    best_of(|| {
+        entity_manager.update_with_delta(0.0).expect("Failed to run the frame");
+        black_box(&entity_manager);
+    })
+}
+
+fn main() {
+    let boxed = bench_boxed_hash_maps();
+    let query = bench_query();
+    let entity_system = bench_entity_system();
+
+    println!("Iterating {} entities with (&mut Position, &Velocity), best of {} runs", ENTITIES, RUNS);
+    println!("  boxed hash maps: {:>10.3?}", boxed);
+    println!("  query:           {:>10.3?}  {:>5.1}x", query, boxed.as_secs_f64() / query.as_secs_f64());
+    println!(
+        "  entity system:   {:>10.3?}  {:>5.1}x",
+        entity_system,
+        boxed.as_secs_f64() / entity_system.as_secs_f64()
+    );
+}
@@ -0,0 +1,46 @@
+// examples/demo.rs
+// Description: The raycasting demo, a player walking around the test world. Run with `cargo run --example demo`.
+use game::app::App;
+use game::error::EngineError;
+use game::game_manager::GameManager;
+use game::input_handler::InputHandler;
+use game::log_info;
+use game::plugins;
+use game::sdl_window_manager::SDLWindowManager;
+use sdl2::Sdl;
+
+fn main() -> Result<(), EngineError> {
+    // Initialize SDL
+    let sdl_context: Sdl = sdl2::init().map_err(EngineError::Sdl)?;
+    
+    // Create an SDL window manager
+    let window_manager = SDLWindowManager::builder()
+        .width(800)
+        .height(800)
+        .title("Game Window")
+        .build()?;
+
+    // Create an input handler
+    let event_pump = sdl_context.event_pump().map_err(EngineError::Sdl)?;
+    let input_handler = InputHandler::new(event_pump);
+
+    // Put the engine together from the features this game uses
+    let mut app = App::new();
+    app.insert_non_send_resource(input_handler)
+        .insert_renderer(window_manager)
+        .add_plugin(plugins::core::CorePlugin)
+        .add_plugin(plugins::player_control::PlayerControlPlugin)
+        .add_plugin(plugins::raycast_render::RaycastRenderPlugin)
+        .add_plugin(plugins::top_down_debug::TopDownDebugPlugin)
+        .add_prefab_dir("assets/prefabs");
+    log_info!("Plugins: {}", app.plugin_names().join(", "));
+
+    // Initialize GameManager with the entity manager the app puts together
+    let mut game_manager = GameManager::new(app)?;
+
+    // Initialize the level and run the game loop
+    game_manager.initialize_level()?;
+    game_manager.run_game_loop();
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+// src/app.rs
+// Description:
+// This module contains the App, which puts an EntityManager together from plugins before the game starts.
+// A Plugin registers everything one engine feature needs (systems, resources, components, events, prefabs),
+// so a game only opts into the features it wants:
+//   let mut app = App::new();
+//   app.add_plugin(CorePlugin).add_plugin(PlayerControlPlugin);
+//   let entity_manager = app.build()?;
+
+use crate::ecs::component::Component;
+use crate::ecs::entity_manager::EntityManager;
+use crate::ecs::prefab::PrefabRegistry;
+use crate::ecs::system::System;
+use crate::error::EngineError;
+use crate::render::Renderer;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::any::TypeId;
+use std::path::PathBuf;
+
+/// One engine feature, added to an App with `add_plugin`
+pub trait Plugin: 'static {
+    fn build(&self, app: &mut App);
+    /// Used when reporting the plugins
+    fn name(&self) -> &'static str {
+        let name = std::any::type_name::<Self>();
+        name.rsplit("::").next().unwrap_or(name)
+    }
+}
+
+pub struct App {
+    entity_manager: EntityManager,
+    prefabs: PrefabRegistry,
+    prefab_dirs: Vec<PathBuf>, // loaded in order by build
+    plugins: Vec<(TypeId, &'static str)>,
+}
+
+impl App {
+    pub fn new() -> Self {
+        App {
+            entity_manager: EntityManager::new(),
+            prefabs: PrefabRegistry::new(),
+            prefab_dirs: Vec::new(),
+            plugins: Vec::new(),
+        }
+    }
+
+    /// Builds the plugin into the app. Adding the same plugin twice does nothing,
+    /// so plugins can add the plugins they depend on themselves.
+    pub fn add_plugin<P: Plugin>(&mut self, plugin: P) -> &mut Self {
+        let type_id = TypeId::of::<P>();
+        if self.plugins.iter().any(|(id, _)| *id == type_id) {
+            return self;
+        }
+        self.plugins.push((type_id, plugin.name()));
+        plugin.build(self);
+        self
+    }
+
+    pub fn has_plugin<P: Plugin>(&self) -> bool {
+        self.plugins.iter().any(|(id, _)| *id == TypeId::of::<P>())
+    }
+
+    /// Names of the added plugins, in the order they were added
+    pub fn plugin_names(&self) -> Vec<&'static str> {
+        self.plugins.iter().map(|(_, name)| *name).collect()
+    }
+
+    pub fn add_system<S: System>(&mut self, system: S) -> &mut Self {
+        self.entity_manager.add_system(system);
+        self
+    }
+
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) -> &mut Self {
+        self.entity_manager.add_event::<T>();
+        self
+    }
+
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, resource: T) -> &mut Self {
+        self.entity_manager.insert_resource(resource);
+        self
+    }
+
+    pub fn insert_non_send_resource<T: 'static>(&mut self, resource: T) -> &mut Self {
+        self.entity_manager.insert_non_send_resource(resource);
+        self
+    }
+
+    /// Sets what the render systems draw with, the SDLWindowManager or a FrameBuffer
+    pub fn insert_renderer<R: Renderer + 'static>(&mut self, renderer: R) -> &mut Self {
+        self.entity_manager.insert_renderer(renderer);
+        self
+    }
+
+    /// Lets save files and prefab files use the component `T` as `name`
+    pub fn register_component<T: Component + Serialize + DeserializeOwned>(&mut self, name: &'static str) -> &mut Self {
+        self.entity_manager.register_component::<T>(name);
+        self
+    }
+
+    /// Lets save files use the resource `T` as `name`
+    pub fn register_resource<T: Send + Sync + Serialize + DeserializeOwned + 'static>(&mut self, name: &'static str) -> &mut Self {
+        self.entity_manager.register_resource::<T>(name);
+        self
+    }
+
+    /// Lets prefab files name the system `S` as `name`, it is added once a loaded prefab names it
+    pub fn register_prefab_system<S: System>(&mut self, name: &str, make: impl Fn() -> S + Send + Sync + 'static) -> &mut Self {
+        self.prefabs.register_system(name, make);
+        self
+    }
+
+    /// Loads every prefab file in the folder when the app is built
+    pub fn add_prefab_dir(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.prefab_dirs.push(path.into());
+        self
+    }
+
+    /// For anything the app has no shortcut for, like component hooks
+    pub fn entity_manager_mut(&mut self) -> &mut EntityManager {
+        &mut self.entity_manager
+    }
+
+    /// Loads the prefabs and hands over the finished entity manager
+    pub fn build(self) -> Result<EntityManager, EngineError> {
+        let App { mut entity_manager, mut prefabs, prefab_dirs, .. } = self;
+        for dir in prefab_dirs {
+            prefabs.load_dir(dir)?;
+        }
+        entity_manager.insert_prefabs(prefabs)?;
+        Ok(entity_manager)
+    }
+}
+
+impl Default for App {
+    fn default() -> Self {
+        Self::new()
+    }
+}
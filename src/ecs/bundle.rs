@@ -0,0 +1,66 @@
+// src/ecs/bundle.rs
+// Description:
+// This module contains the Bundle trait, a group of components that is inserted onto an entity in one call.
+// It is implemented for tuples of components so prefabs can write `spawn((Transform::default(), CollisionData))`.
+
+use crate::ecs::component::Component;
+use crate::ecs::entity_manager::{Entity, EntityManager};
+
+pub trait Bundle {
+    /// Adds every component in the bundle to the entity
+    fn insert_into(self, entity_manager: &mut EntityManager, entity: &Entity);
+}
+
+// Implements Bundle for tuples of components, each component is added in order
+macro_rules! impl_bundle_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: Component + 'static),*> Bundle for ($($name,)*) {
+            fn insert_into(self, entity_manager: &mut EntityManager, entity: &Entity) {
+                let ($($name,)*) = self;
+                $(entity_manager.add_component(entity, $name);)*
+            }
+        }
+    };
+}
+
+impl_bundle_tuple!(A);
+impl_bundle_tuple!(A, B);
+impl_bundle_tuple!(A, B, C);
+impl_bundle_tuple!(A, B, C, D);
+impl_bundle_tuple!(A, B, C, D, E);
+impl_bundle_tuple!(A, B, C, D, E, F);
+impl_bundle_tuple!(A, B, C, D, E, F, G);
+impl_bundle_tuple!(A, B, C, D, E, F, G, H);
+
+#[cfg(test)]
+mod tests {
+    use crate::ecs::component::Component;
+    use crate::ecs::entity_manager::EntityManager;
+
+    struct Health(u32);
+    impl Component for Health {}
+
+    struct Armor(u32);
+    impl Component for Armor {}
+
+    #[test]
+    fn spawning_a_bundle_adds_every_component() {
+        let mut entity_manager = EntityManager::new();
+        let entity = entity_manager.spawn((Health(10), Armor(5)));
+
+        assert!(entity_manager.is_alive(&entity));
+        assert_eq!(entity_manager.get_component::<Health>(&entity).map(|health| health.0), Some(10));
+        assert_eq!(entity_manager.get_component::<Armor>(&entity).map(|armor| armor.0), Some(5));
+    }
+
+    #[test]
+    fn adding_a_bundle_replaces_existing_components() {
+        let mut entity_manager = EntityManager::new();
+        let entity = entity_manager.spawn((Health(10),));
+        entity_manager.add_components(&entity, (Health(1), Armor(2)));
+
+        assert_eq!(entity_manager.get_component::<Health>(&entity).map(|health| health.0), Some(1));
+        assert_eq!(entity_manager.get_component::<Armor>(&entity).map(|armor| armor.0), Some(2));
+    }
+}
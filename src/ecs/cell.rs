@@ -0,0 +1,156 @@
+// src/ecs/cell.rs
+// Description:
+// This module contains the interior mutability behind parallel systems. While a batch of systems runs they all
+// share one &EntityManager, so component storages and resources live in cells that can hand out mutable access
+// through a shared borrow. The scheduler only puts systems with non-conflicting access in the same batch and
+// SystemContext checks every borrow against what its system declared, which is what keeps this sound.
+// Resources that can't leave the main thread (the SDL window and event pump) get their own map that checks the thread.
+
+use std::any::{Any, TypeId};
+use std::cell::UnsafeCell;
+use std::collections::HashMap;
+use std::thread::{self, ThreadId};
+
+/// A value that systems running in parallel can reach through a shared borrow
+pub struct SystemCell<T: ?Sized>(UnsafeCell<Box<T>>);
+
+// Safety: the value itself is Send + Sync, overlapping mutable access is ruled out by the scheduler
+unsafe impl<T: ?Sized + Send + Sync> Sync for SystemCell<T> {}
+
+impl<T: ?Sized> SystemCell<T> {
+    pub fn new(value: Box<T>) -> Self {
+        SystemCell(UnsafeCell::new(value))
+    }
+
+    /// Shared access, only valid while nobody writes through `as_ptr`, which the declared access guarantees
+    pub fn get(&self) -> &T {
+        unsafe { &*self.0.get() }
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.0.get_mut()
+    }
+
+    pub fn into_inner(self) -> Box<T> {
+        self.0.into_inner()
+    }
+
+    /// # Safety
+    /// No other borrow of the value may be alive while the pointer is written through
+    pub unsafe fn as_ptr(&self) -> *mut T {
+        std::ptr::addr_of_mut!(**self.0.get())
+    }
+}
+
+/// Resources that are not Send or Sync, like the SDL window. They can only be reached from the thread
+/// that created the entity manager, systems using them declare `main_thread` so they are never moved off it.
+pub(crate) struct NonSendResources {
+    owner: ThreadId,
+    values: HashMap<TypeId, SystemCell<dyn Any>>,
+}
+
+// Safety: every access checks that it happens on the owning thread, other threads can only pass the map around
+unsafe impl Sync for NonSendResources {}
+
+impl NonSendResources {
+    pub fn new() -> Self {
+        NonSendResources {
+            owner: thread::current().id(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn check_thread(&self) {
+        if thread::current().id() != self.owner {
+            panic!("Non-send resources can only be used on the main thread");
+        }
+    }
+
+    pub fn insert<T: 'static>(&mut self, value: T) -> Option<T> {
+        self.check_thread();
+        self.values
+            .insert(TypeId::of::<T>(), SystemCell::new(Box::new(value)))
+            .and_then(|old| old.into_inner().downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn remove<T: 'static>(&mut self) -> Option<T> {
+        self.check_thread();
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.into_inner().downcast::<T>().ok())
+            .map(|value| *value)
+    }
+
+    pub fn contains<T: 'static>(&self) -> bool {
+        self.values.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn get<T: 'static>(&self) -> Option<&T> {
+        self.check_thread();
+        self.values.get(&TypeId::of::<T>()).and_then(|value| value.get().downcast_ref::<T>())
+    }
+
+    pub fn get_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.check_thread();
+        self.values
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|value| value.get_mut().downcast_mut::<T>())
+    }
+
+    /// # Safety
+    /// No other borrow of the resource may be alive while the returned one is
+    #[allow(clippy::mut_from_ref)]
+    pub unsafe fn get_unchecked_mut<T: 'static>(&self) -> Option<&mut T> {
+        self.check_thread();
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| (*value.as_ptr()).downcast_mut::<T>())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entity_manager::EntityManager;
+
+    struct Score(u32);
+
+    #[test]
+    fn resources_round_trip() {
+        let mut entity_manager = EntityManager::new();
+        assert!(entity_manager.insert_resource(Score(1)).is_none());
+        if let Some(score) = entity_manager.resource_mut::<Score>() {
+            score.0 += 1;
+        }
+        assert_eq!(entity_manager.resource::<Score>().map(|score| score.0), Some(2));
+
+        // Inserting again hands back the old value
+        assert_eq!(entity_manager.insert_resource(Score(10)).map(|score| score.0), Some(2));
+        assert_eq!(entity_manager.remove_resource::<Score>().map(|score| score.0), Some(10));
+        assert!(!entity_manager.has_resource::<Score>());
+    }
+
+    #[test]
+    fn non_send_resources_round_trip_on_the_owning_thread() {
+        let mut resources = NonSendResources::new();
+        resources.insert(Score(1));
+        if let Some(score) = resources.get_mut::<Score>() {
+            score.0 += 1;
+        }
+        assert_eq!(resources.get::<Score>().map(|score| score.0), Some(2));
+        assert_eq!(resources.remove::<Score>().map(|score| score.0), Some(2));
+        assert!(!resources.contains::<Score>());
+    }
+
+    #[test]
+    fn non_send_resources_panic_on_another_thread() {
+        let mut resources = NonSendResources::new();
+        resources.insert(Score(1));
+
+        let result = thread::scope(|scope| scope.spawn(|| resources.get::<Score>().map(|score| score.0)).join());
+        let panic = result.expect_err("Reading a non-send resource off the main thread should panic");
+        let message = panic.downcast_ref::<&str>().copied().unwrap_or_default();
+        assert!(message.contains("only be used on the main thread"), "{}", message);
+    }
+}
@@ -0,0 +1,222 @@
+// src/ecs/commands.rs
+// Description:
+// This module contains the command buffer used for structural changes (spawn, despawn, adding and removing components)
+// while systems are running. Commands are queued up and applied by the system manager once the running system
+// is done, so no system ever sees entities appear or disappear halfway through its update.
+// Systems running in parallel each fill their own queue, the queues are applied in schedule order after the batch.
+
+use crate::ecs::bundle::Bundle;
+use crate::ecs::component::Component;
+use crate::ecs::entity_manager::{Entities, Entity, EntityManager};
+
+type Command = Box<dyn FnOnce(&mut EntityManager) + Send + Sync>;
+
+/// The queued commands, owned by the entity manager
+#[derive(Default)]
+pub struct CommandQueue {
+    commands: Vec<Command>,
+    reserved: Vec<Entity>, // handles reserved by queued spawns, freed again if the queue is discarded
+}
+
+impl CommandQueue {
+    pub fn push(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Moves every command of the other queue to the end of this one
+    pub fn append(&mut self, other: &mut CommandQueue) {
+        self.commands.append(&mut other.commands);
+        self.reserved.append(&mut other.reserved);
+    }
+
+    // Hands the queued commands over for applying and leaves the queue empty
+    pub fn take(&mut self) -> Vec<Command> {
+        self.reserved.clear();
+        std::mem::take(&mut self.commands)
+    }
+
+    // Drops the queued commands without applying them, returns the handles their spawns reserved
+    pub(crate) fn discard(&mut self) -> Vec<Entity> {
+        self.commands.clear();
+        std::mem::take(&mut self.reserved)
+    }
+}
+
+/// Short-lived handle for queueing commands, get one from `EntityManager::commands` or `SystemContext::commands`
+pub struct Commands<'a> {
+    entities: &'a Entities,
+    queue: &'a mut CommandQueue,
+}
+
+impl<'a> Commands<'a> {
+    pub(crate) fn new(entities: &'a Entities, queue: &'a mut CommandQueue) -> Self {
+        Commands { entities, queue }
+    }
+
+    /// Queues spawning an entity with the bundle. The handle is reserved right away so it can be
+    /// used in later commands, but the entity is not alive until the commands are applied.
+    pub fn spawn<B: Bundle + Send + Sync + 'static>(&mut self, bundle: B) -> Entity {
+        let entity = self.entities.reserve();
+        self.queue.reserved.push(entity);
+        self.add(move |entity_manager| {
+            entity_manager.spawn_reserved(&entity);
+            bundle.insert_into(entity_manager, &entity);
+        });
+        entity
+    }
+
+    pub fn despawn(&mut self, entity: Entity) {
+        self.add(move |entity_manager| {
+            entity_manager.despawn(&entity);
+        });
+    }
+
+    pub fn add_component<T: Component + 'static>(&mut self, entity: Entity, component: T) {
+        self.add(move |entity_manager| entity_manager.add_component(&entity, component));
+    }
+
+    pub fn add_components<B: Bundle + Send + Sync + 'static>(&mut self, entity: Entity, bundle: B) {
+        self.add(move |entity_manager| entity_manager.add_components(&entity, bundle));
+    }
+
+    pub fn remove_component<T: Component + 'static>(&mut self, entity: Entity) {
+        self.add(move |entity_manager| {
+            entity_manager.remove_component::<T>(&entity);
+        });
+    }
+
+    pub fn set_parent(&mut self, child: Entity, parent: Entity) {
+        self.add(move |entity_manager| {
+            entity_manager.set_parent(&child, &parent);
+        });
+    }
+
+    pub fn despawn_recursive(&mut self, entity: Entity) {
+        self.add(move |entity_manager| {
+            entity_manager.despawn_recursive(&entity);
+        });
+    }
+
+    /// Queues any other change that needs the whole entity manager
+    pub fn add<F: FnOnce(&mut EntityManager) + Send + Sync + 'static>(&mut self, command: F) {
+        self.queue.push(Box::new(command));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::system::access::SystemAccess;
+    use crate::ecs::system::context::SystemContext;
+    use crate::ecs::system::{Stage, System};
+    use crate::error::EngineError;
+    use std::any::Any;
+    use std::thread;
+    use std::time::Duration;
+
+    struct Health(u32);
+    impl Component for Health {}
+
+    // Entities spawned by the systems, in the order their commands were applied
+    #[derive(Default)]
+    struct Spawned(Vec<(usize, Entity)>);
+
+    // Spawns one entity per run and queues a command recording it. The first one is slowed down so it finishes
+    // after the others when the batch runs in parallel.
+    struct Spawner<const ID: usize>;
+
+    impl<const ID: usize> System for Spawner<ID> {
+        fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+            if ID == 0 {
+                thread::sleep(Duration::from_millis(20));
+            }
+            let mut commands = context.commands();
+            let entity = commands.spawn((Health(ID as u32),));
+            commands.add(move |entity_manager| {
+                if let Some(spawned) = entity_manager.resource_mut::<Spawned>() {
+                    spawned.0.push((ID, entity));
+                }
+            });
+            assert!(!context.is_alive(&entity), "a reserved entity is alive before the commands are applied");
+            Ok(())
+        }
+
+        // Runs once a frame whatever the frame time
+        fn stage(&self) -> Stage {
+            Stage::PostPhysics
+        }
+
+        fn access(&self) -> SystemAccess {
+            SystemAccess::new()
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn commands_of_a_parallel_batch_apply_in_schedule_order() {
+        let mut entity_manager = EntityManager::new();
+        entity_manager.insert_resource(Spawned::default());
+        entity_manager.add_system(Spawner::<0>);
+        entity_manager.add_system(Spawner::<1>);
+        entity_manager.add_system(Spawner::<2>);
+        entity_manager.update_with_delta(0.0).unwrap();
+
+        let spawned = &entity_manager.resource::<Spawned>().unwrap().0;
+        let order: Vec<usize> = spawned.iter().map(|(id, _)| *id).collect();
+        assert_eq!(order, [0, 1, 2]);
+        // Every reservation got its own id and is alive now
+        for (id, entity) in spawned {
+            assert!(entity_manager.is_alive(entity));
+            assert_eq!(entity_manager.get_component::<Health>(entity).map(|health| health.0), Some(*id as u32));
+        }
+        let mut ids: Vec<u32> = spawned.iter().map(|(_, entity)| entity.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), 3);
+    }
+
+    #[test]
+    fn reserved_entities_only_come_alive_when_applied() {
+        let mut entity_manager = EntityManager::new();
+        let entity = entity_manager.commands().spawn((Health(1),));
+        assert!(!entity_manager.is_alive(&entity));
+        assert!(entity_manager.get_component::<Health>(&entity).is_none());
+
+        entity_manager.apply_commands();
+        assert!(entity_manager.is_alive(&entity));
+        assert_eq!(entity_manager.get_component::<Health>(&entity).map(|health| health.0), Some(1));
+    }
+
+    #[test]
+    fn despawn_then_spawn_recycles_the_id_with_a_new_generation() {
+        let mut entity_manager = EntityManager::new();
+        let old = entity_manager.spawn((Health(1),));
+
+        // Queued together the spawn can't reuse the id yet, it is only freed when the despawn is applied
+        let mut commands = entity_manager.commands();
+        commands.despawn(old);
+        let queued_together = commands.spawn((Health(2),));
+        entity_manager.apply_commands();
+        assert_ne!(queued_together.id, old.id);
+        assert!(!entity_manager.is_alive(&old));
+
+        let recycled = entity_manager.commands().spawn((Health(3),));
+        entity_manager.apply_commands();
+        assert_eq!(recycled.id, old.id);
+        assert_eq!(recycled.generation, old.generation + 1);
+        assert!(entity_manager.is_alive(&recycled));
+        assert!(!entity_manager.is_alive(&old));
+        assert_eq!(entity_manager.get_component::<Health>(&recycled).map(|health| health.0), Some(3));
+    }
+}
@@ -1,16 +1,29 @@
 use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
 // Define a struct to hold camera data
+#[derive(Serialize, Deserialize)]
 pub struct CameraData {
     pub fov: i32, // Field of view in degrees
 }
 
+impl Default for CameraData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl CameraData {
     // Constructor for CameraData, initializes with a default FOV of 60 degrees
     pub fn new() -> Self {
         CameraData { fov: 60 }
     }
 
+    // Constructor for CameraData with a given FOV in degrees
+    pub fn with_fov(fov: i32) -> Self {
+        CameraData { fov }
+    }
+
     // Calculate the camera plane based on direction vectors
     pub fn calculate_camera_plane(&self, dir_x: f32, dir_y: f32) -> (f32, f32) {
         // Convert FOV from degrees to radians
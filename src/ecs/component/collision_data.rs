@@ -1,6 +1,7 @@
 use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct CollisionData;
 
 impl Component for CollisionData{} //implementation of component for collision data
\ No newline at end of file
@@ -0,0 +1,45 @@
+// src/ecs/component/hierarchy.rs
+// Description:
+// Components linking entities into a parent/child hierarchy. A child's Transform is relative to its parent,
+// the TransformPropagationSystem turns it into a world-space GlobalTransform every frame.
+// Both components are managed by `EntityManager::set_parent` and `remove_parent` so they always agree.
+
+use crate::ecs::component::Component;
+use crate::ecs::entity_manager::Entity;
+use serde::{Deserialize, Serialize};
+
+/// The entity this one is attached to
+#[derive(Serialize, Deserialize)]
+pub struct Parent(pub(crate) Entity);
+
+impl Parent {
+    pub fn get(&self) -> Entity {
+        self.0
+    }
+}
+
+impl Component for Parent {}
+
+/// The entities attached to this one, in the order they were attached
+#[derive(Default, Serialize, Deserialize)]
+pub struct Children(pub(crate) Vec<Entity>);
+
+impl Children {
+    pub fn iter(&self) -> std::slice::Iter<'_, Entity> {
+        self.0.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn contains(&self, entity: &Entity) -> bool {
+        self.0.contains(entity)
+    }
+}
+
+impl Component for Children {}
@@ -3,6 +3,7 @@
 //Description:
 //This module contains components that can be attached to entities. Components are data that can be attached to entities to give them properties.
 //Components are used by systems to update entities. They do not contain logic.
+//Components are shared with systems running on other threads, so they have to be Send + Sync.
 
 pub mod transform_data;
 pub mod collision_data;
@@ -10,4 +11,17 @@ pub mod render_data;
 pub mod player_data;
 pub mod world_data;
 pub mod camera_data;
-pub trait Component {} //trait for component
\ No newline at end of file
+pub mod hierarchy;
+pub mod name;
+pub mod tags;
+pub mod sprite;
+pub trait Component: Send + Sync + 'static { //trait for component
+    /// Components other data is derived from, like Name and its index, turn this off. They can't be borrowed
+    /// mutably then and are changed by adding a new one, so the hooks see the change.
+    const MUTABLE: bool = true;
+}
+
+// Fails to compile wherever a component that isn't MUTABLE is borrowed mutably
+pub(crate) fn assert_mutable<T: Component>() {
+    const { assert!(T::MUTABLE, "This component can't be borrowed mutably, add a new one to change it") }
+}
\ No newline at end of file
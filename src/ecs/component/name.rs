@@ -0,0 +1,33 @@
+// src/ecs/component/name.rs
+// Description:
+// The Name component gives an entity a human readable name, used to find it with `EntityManager::find_by_name`
+// and to say which entity something went wrong with. Names don't have to be unique.
+// To rename an entity add a new Name, the name index is only updated when the component is added or removed,
+// so Name can't be borrowed mutably.
+
+use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Name(String);
+
+impl Name {
+    pub fn new(name: impl Into<String>) -> Self {
+        Name(name.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Component for Name {
+    const MUTABLE: bool = false;
+}
@@ -1,12 +1,19 @@
 use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct PlayerData{
     fov: i32,
 }
 
 impl Component for PlayerData{}
 
+impl Default for PlayerData {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PlayerData{
     pub fn new() -> PlayerData{
         PlayerData{
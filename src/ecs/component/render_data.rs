@@ -1,6 +1,7 @@
 use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub struct RenderData {
     pub size: f32,
     pub r: f32,
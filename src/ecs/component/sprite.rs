@@ -0,0 +1,25 @@
+// src/ecs/component/sprite.rs
+// Description: This module contains the Sprite component. Entities with a Sprite are drawn as billboards in the
+// first person view, always facing the camera and standing on the floor of their position.
+
+use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Sprite {
+    pub texture: u32, // index into the TextureAtlas, pixels with an alpha of 0 are see-through
+    #[serde(default = "default_scale")]
+    pub scale: f32,   // height compared to a wall
+}
+
+fn default_scale() -> f32 {
+    1.0
+}
+
+impl Sprite {
+    pub fn new(texture: u32, scale: f32) -> Self {
+        Sprite { texture, scale }
+    }
+}
+
+impl Component for Sprite {}
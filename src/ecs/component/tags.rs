@@ -0,0 +1,23 @@
+// src/ecs/component/tags.rs
+// Description:
+// Tags are components without data, they only mark an entity so queries can pick it out, e.g.
+// `entity_manager.query_filtered::<Entity, With<Pickup>>()`. The tag values themselves are zero-sized, the storage
+// still keeps the usual per-entity bookkeeping (index and change ticks) for them.
+// New tags are declared with the `tag!` macro, which also makes them usable in save and prefab files.
+
+/// Declares a zero-sized tag component, e.g. `tag!(Enemy);`
+#[macro_export]
+macro_rules! tag {
+    ($(#[$meta:meta])* $name:ident) => {
+        $(#[$meta])*
+        #[derive(Clone, Copy, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+        pub struct $name;
+
+        impl $crate::ecs::component::Component for $name {}
+    };
+}
+
+tag!(
+    /// Something the player can pick up
+    Pickup
+);
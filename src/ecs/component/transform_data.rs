@@ -1,12 +1,16 @@
 use crate::ecs::component::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Default)]
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Transform {
     pub position: Position,
     pub velocity: Velocity,
 }
 
-#[derive(Clone, Default)]
+// Saved without the direction, which is worked out from the rotation again when loading
+#[derive(Clone, Default, Serialize, Deserialize)]
+#[serde(from = "SavedPosition", into = "SavedPosition")]
 pub struct Position {
     x: f32,
     y: f32,
@@ -14,14 +18,56 @@ pub struct Position {
     dir_x: f32,
     dir_y: f32,
 }
-#[derive(Clone, Default)]
+/// Units per second, `delta_x` along the facing direction and `delta_y` to the side.
+/// The direction vectors are 10 units long, so a delta of 1 moves 10 pixels a second.
+#[derive(Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Velocity {
     pub delta_x: f32,
     pub delta_y: f32,
+    pub rotation: f32, // radians per second
 }
 
 impl Component for Transform {}
 
+/// World-space position of an entity, written by the TransformPropagationSystem from the Transform and the
+/// entity's parents. Read it for drawing, change the Transform to move the entity.
+#[derive(Clone, Default)]
+pub struct GlobalTransform {
+    pub position: Position,
+}
+
+impl Component for GlobalTransform {}
+
+/// The Transform's position at the start of the current simulation step, kept so drawing can interpolate between
+/// steps. Added and updated by the entity manager, don't change it yourself.
+#[derive(Clone, Default)]
+pub struct PreviousTransform {
+    pub position: Position,
+}
+
+impl Component for PreviousTransform {}
+
+#[derive(Serialize, Deserialize)]
+struct SavedPosition {
+    x: f32,
+    y: f32,
+    #[serde(default)]
+    rotation: f32,
+}
+
+impl From<SavedPosition> for Position {
+    fn from(saved: SavedPosition) -> Self {
+        Position::new(saved.x, saved.y, saved.rotation)
+    }
+}
+
+impl From<Position> for SavedPosition {
+    fn from(position: Position) -> Self {
+        SavedPosition { x: position.x, y: position.y, rotation: position.rotation }
+    }
+}
+
 impl Position {
     pub fn new(x: f32, y: f32, rotation: f32) -> Self {
         Position {
@@ -45,6 +91,37 @@ impl Position {
         self.dir_y = self.rotation.sin() * 10.0;
     }
 
+    /// Blends from this position to `other`, `t` = 0 gives this one and 1 gives `other`.
+    /// The rotation turns the short way round.
+    pub fn lerp(&self, other: &Position, t: f32) -> Position {
+        let two_pi = 2.0 * std::f32::consts::PI;
+        let mut turn = (other.rotation - self.rotation) % two_pi;
+        if turn > std::f32::consts::PI {
+            turn -= two_pi;
+        } else if turn < -std::f32::consts::PI {
+            turn += two_pi;
+        }
+        let mut position = Position::new(
+            self.x + (other.x - self.x) * t,
+            self.y + (other.y - self.y) * t,
+            self.rotation + turn * t,
+        );
+        position.normalize_rotation();
+        position
+    }
+
+    /// Takes a position given relative to this one and returns it in the space this one lives in
+    pub fn compose(&self, local: &Position) -> Position {
+        let (sin, cos) = self.rotation.sin_cos();
+        let mut position = Position::new(
+            self.x + local.x * cos - local.y * sin,
+            self.y + local.x * sin + local.y * cos,
+            self.rotation + local.rotation,
+        );
+        position.normalize_rotation();
+        position
+    }
+
     pub fn forward_vector(&self) -> (f32, f32) {
         (self.dir_x, self.dir_y)
     }
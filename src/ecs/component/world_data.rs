@@ -1,47 +1,120 @@
 // src/ecs/component/world_data.rs
-// Description: This module contains the WorldData component, which stores information about the world.
+// Description: This module contains WorldData, which stores information about the world.
+// There is only ever one map so it is stored as a resource on the EntityManager rather than as a component.
 
-use super::Component;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(try_from = "UncheckedWorldData")]
 pub struct WorldData {
     pub world_size: u32,    // Width and height of the world (in tiles)
     pub cell_size: u32,     // Size of each square tile
     pub walls: Vec<Vec<bool>>, // 2D vector to represent walls
+    // Index into the TextureAtlas for every wall tile, same layout as walls. Tiles missing from it use texture 0
+    #[serde(default)]
+    pub wall_textures: Vec<Vec<u32>>,
+    // What the floor and ceiling of every tile look like, same layout as walls. Tiles missing from them use
+    // DEFAULT_FLOOR and DEFAULT_CEILING
+    #[serde(default)]
+    pub floors: Vec<Vec<Surface>>,
+    #[serde(default)]
+    pub ceilings: Vec<Vec<Surface>>,
 }
 
+// WorldData as it is read from a save, checked before it is used because is_wall indexes walls directly
+#[derive(Deserialize)]
+struct UncheckedWorldData {
+    world_size: u32,
+    cell_size: u32,
+    walls: Vec<Vec<bool>>,
+    #[serde(default)]
+    wall_textures: Vec<Vec<u32>>,
+    #[serde(default)]
+    floors: Vec<Vec<Surface>>,
+    #[serde(default)]
+    ceilings: Vec<Vec<Surface>>,
+}
+
+impl TryFrom<UncheckedWorldData> for WorldData {
+    type Error = String;
+
+    // walls has to be world_size rows of world_size tiles, the other layers are looked up with a fallback
+    fn try_from(data: UncheckedWorldData) -> Result<Self, Self::Error> {
+        let size = data.world_size as usize;
+        if data.walls.len() != size {
+            return Err(format!("walls has {} rows, expected {}", data.walls.len(), size));
+        }
+        if let Some(y) = data.walls.iter().position(|row| row.len() != size) {
+            return Err(format!("Row {} of walls has {} tiles, expected {}", y, data.walls[y].len(), size));
+        }
+        Ok(WorldData {
+            world_size: data.world_size,
+            cell_size: data.cell_size,
+            walls: data.walls,
+            wall_textures: data.wall_textures,
+            floors: data.floors,
+            ceilings: data.ceilings,
+        })
+    }
+}
+
+/// How a floor or ceiling tile is drawn
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Surface {
+    Texture(u32), // index into the TextureAtlas
+    Color(u8, u8, u8),
+}
+
+pub const DEFAULT_FLOOR: Surface = Surface::Color(100, 100, 100);
+pub const DEFAULT_CEILING: Surface = Surface::Color(60, 60, 70);
+
 pub struct Wall {
     pub x: u32,
     pub y: u32,
 }
 
-impl Component for WorldData {}
-
 impl WorldData {
     pub fn new(tiles: u32, window_width: u32, window_height: u32) -> WorldData {
         let min_dimension = window_width.min(window_height);
         let cell_size = min_dimension / tiles;
         let mut walls = vec![vec![false; tiles as usize]; tiles as usize]; // Creates a square grid
+        let mut wall_textures = vec![vec![0; tiles as usize]; tiles as usize];
         
-        // Draw wall along the top and bottom
+        // Draw wall along the top and bottom, in brick
         for x in 0..tiles {
             walls[0][x as usize] = true;
             walls[tiles as usize - 1][x as usize] = true;
         }
     
-        // Draw wall along the left and right
-        for y in 0..tiles {
+        // Draw wall along the left and right, in stone
+        for y in 1..tiles - 1 {
             walls[y as usize][0] = true;
             walls[y as usize][tiles as usize - 1] = true;
+            wall_textures[y as usize][0] = 1;
+            wall_textures[y as usize][tiles as usize - 1] = 1;
         }
     
-        // Draw one in the middle
+        // Draw one in the middle, in wood
         walls[tiles as usize / 2][tiles as usize / 2] = true;
+        wall_textures[tiles as usize / 2][tiles as usize / 2] = 2;
+
+        // Metal floors with a stone patch around the middle, under a plain ceiling
+        let mut floors = vec![vec![Surface::Texture(3); tiles as usize]; tiles as usize];
+        let center = tiles / 2;
+        for y in center.saturating_sub(2)..(center + 3).min(tiles) {
+            for x in center.saturating_sub(2)..(center + 3).min(tiles) {
+                floors[y as usize][x as usize] = Surface::Texture(1);
+            }
+        }
+        let ceilings = vec![vec![DEFAULT_CEILING; tiles as usize]; tiles as usize];
     
         WorldData {
             world_size: tiles,
             cell_size,
             walls,
+            wall_textures,
+            floors,
+            ceilings,
         }
     }
 
@@ -58,6 +131,34 @@ impl WorldData {
         self.walls[y as usize][x as usize]
     }
 
+    // Returns the texture index of the wall at the tile, 0 if the map doesn't say
+    pub fn wall_texture(&self, x: i32, y: i32) -> usize {
+        if x < 0 || y < 0 {
+            return 0;
+        }
+        self.wall_textures
+            .get(y as usize)
+            .and_then(|row| row.get(x as usize))
+            .map_or(0, |&texture| texture as usize)
+    }
+
+    // Returns the floor of the tile, DEFAULT_FLOOR outside the map or if the map doesn't say
+    pub fn floor(&self, x: i32, y: i32) -> Surface {
+        Self::surface(&self.floors, x, y).unwrap_or(DEFAULT_FLOOR)
+    }
+
+    // Returns the ceiling of the tile, DEFAULT_CEILING outside the map or if the map doesn't say
+    pub fn ceiling(&self, x: i32, y: i32) -> Surface {
+        Self::surface(&self.ceilings, x, y).unwrap_or(DEFAULT_CEILING)
+    }
+
+    fn surface(surfaces: &[Vec<Surface>], x: i32, y: i32) -> Option<Surface> {
+        if x < 0 || y < 0 {
+            return None;
+        }
+        surfaces.get(y as usize).and_then(|row| row.get(x as usize)).copied()
+    }
+
     // Returns the size of each tile
     pub fn get_cell_size(&self) -> u32 {
         self.cell_size
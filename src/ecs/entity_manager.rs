@@ -1,93 +1,869 @@
 /// src/ecs/entity_manager.rs
-use crate::ecs::component::Component;
+use crate::ecs::bundle::Bundle;
+use crate::ecs::cell::{NonSendResources, SystemCell};
+use crate::ecs::commands::{CommandQueue, Commands};
+use crate::ecs::component::{assert_mutable, Component};
+use crate::ecs::component::hierarchy::{Children, Parent};
+use crate::ecs::component::name::Name;
+use crate::ecs::event::{EventWriter, Events};
+use crate::ecs::names::NameIndex;
+use crate::ecs::query::{Access, ComponentMap, Query, QueryFilter, QueryIter, ReadOnlyQuery};
+use crate::ecs::snapshot::SnapshotRegistry;
+use crate::ecs::storage::{ComponentStorage, Mut, SparseSet};
 use crate::ecs::system::System;
 use crate::ecs::system::system_manager::SystemManager;
-use crate::game_manager::GameManager;
+use crate::ecs::time::Time;
+use crate::error::{EngineError, ErrorPolicy};
+use crate::render::Renderer;
+use serde::{Deserialize, Serialize};
 use std::any::{Any, TypeId};
 use std::collections::HashMap;
-use std::rc::Rc;
-use std::cell::RefCell;
+use std::fmt;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
 
-#[derive(Clone)]
+/// A handle to an entity. The `id` is an index that gets recycled after the entity is despawned,
+/// the `generation` is bumped every time that happens so an old handle can never alias a new entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub struct Entity {
     pub id: u32,
+    pub generation: u32,
 }
 
+// Written as `id`v`generation`, e.g. 3v0
+impl fmt::Display for Entity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}v{}", self.id, self.generation)
+    }
+}
+
+/// Keeps track of which entity ids are in use. Reserving an id only needs a shared borrow,
+/// so systems running in parallel can spawn entities through their Commands.
+pub struct Entities {
+    next_id: AtomicU32,
+    generations: Vec<u32>,       // current generation of every index spawned so far, later indices are still at 0
+    alive: Vec<bool>,            // whether the index is currently in use
+    free_ids: Mutex<Vec<u32>>,   // despawned indices waiting to be reused
+}
+
+impl Entities {
+    fn new() -> Self {
+        Entities {
+            next_id: AtomicU32::new(0),
+            generations: Vec::new(),
+            alive: Vec::new(),
+            free_ids: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Hands out a handle without bringing the entity to life yet
+    pub fn reserve(&self) -> Entity {
+        // Reuse a despawned index if there is one, its generation was already bumped on despawn
+        if let Some(id) = self.free_ids.lock().unwrap().pop() {
+            return Entity { id, generation: self.generation(id) };
+        }
+        Entity { id: self.next_id.fetch_add(1, Ordering::Relaxed), generation: 0 }
+    }
+
+    fn generation(&self, id: u32) -> u32 {
+        self.generations.get(id as usize).copied().unwrap_or(0)
+    }
+
+    // Brings a reserved entity to life, does nothing if the handle was never reserved
+    fn spawn_reserved(&mut self, entity: &Entity) {
+        if entity.id >= *self.next_id.get_mut() || self.generation(entity.id) != entity.generation {
+            return;
+        }
+        let index = entity.id as usize;
+        if index >= self.alive.len() {
+            self.alive.resize(index + 1, false);
+            self.generations.resize(index + 1, 0);
+        }
+        self.alive[index] = true;
+    }
+
+    // Gives back a reserved handle that was never spawned, so its index isn't lost
+    fn release(&mut self, entity: &Entity) {
+        if entity.id >= *self.next_id.get_mut() || self.generation(entity.id) != entity.generation || self.is_alive(entity) {
+            return;
+        }
+        self.free_ids.get_mut().unwrap().push(entity.id);
+    }
+
+    // Marks the index as free and bumps its generation so old handles go stale
+    fn free(&mut self, entity: &Entity) {
+        let index = entity.id as usize;
+        self.alive[index] = false;
+        self.generations[index] = self.generations[index].wrapping_add(1);
+        self.free_ids.get_mut().unwrap().push(entity.id);
+    }
+
+    /// Checks that the handle still refers to a living entity and not a recycled index
+    pub fn is_alive(&self, entity: &Entity) -> bool {
+        let index = entity.id as usize;
+        index < self.alive.len() && self.alive[index] && self.generations[index] == entity.generation
+    }
+
+    // Builds the handle for a living index, used when walking the component maps
+    fn entity_from_id(&self, id: u32) -> Option<Entity> {
+        let index = id as usize;
+        if index < self.alive.len() && self.alive[index] {
+            Some(Entity { id, generation: self.generations[index] })
+        } else {
+            None
+        }
+    }
+
+    /// Every living entity, in id order
+    pub fn iter(&self) -> impl Iterator<Item = Entity> + '_ {
+        (0..self.id_bound()).filter_map(|id| self.entity_from_id(id))
+    }
+
+    // Brings back entities with exactly these handles on an empty allocator, used when loading a snapshot
+    fn restore(&mut self, entities: &[Entity]) {
+        let bound = entities.iter().map(|entity| entity.id + 1).max().unwrap_or(0);
+        self.alive = vec![false; bound as usize];
+        self.generations = vec![0; bound as usize];
+        for entity in entities {
+            self.alive[entity.id as usize] = true;
+            self.generations[entity.id as usize] = entity.generation;
+        }
+        *self.next_id.get_mut() = bound;
+        *self.free_ids.get_mut().unwrap() = (0..bound).rev().filter(|id| !self.alive[*id as usize]).collect();
+    }
+
+    /// Number of indices handed out so far, every id is below this
+    fn id_bound(&self) -> u32 {
+        self.alive.len() as u32
+    }
+
+    fn count(&self) -> usize {
+        self.alive.iter().filter(|alive| **alive).count()
+    }
+}
+
+/// Callback run when a component of a given type is added to or removed from an entity
+pub type ComponentHook = Arc<dyn Fn(&mut EntityManager, Entity) + Send + Sync>;
+
+#[derive(Default)]
+struct ComponentHooks {
+    on_add: Vec<ComponentHook>,
+    on_remove: Vec<ComponentHook>,
+}
+
+/// Owns every entity, component, resource and system. Everything but the non-send resources is Send + Sync
+/// so systems can share it while they run in parallel.
 pub struct EntityManager {
-    next_id: u32,
-    entities: Vec<Entity>,
-    components: HashMap<TypeId, HashMap<u32, Box<dyn Any>>>,
-    system_manager: Rc<RefCell<SystemManager>>,
-    game_manager: Rc<RefCell<GameManager>>,
+    entities: Entities,
+    components: ComponentMap,
+    hooks: HashMap<TypeId, ComponentHooks>,
+    change_tick: u32,   // bumped every time a system runs, components remember the tick they were added/changed at
+    last_run_tick: u32, // the tick the last frame started at, used by the Added and Changed filters outside of systems
+    command_queue: CommandQueue,
+    resources: HashMap<TypeId, SystemCell<dyn Any + Send + Sync>>, // one value per type, shared by every system
+    non_send_resources: NonSendResources, // resources tied to the main thread, like the window
+    event_updaters: Vec<fn(&mut EntityManager)>, // swaps the buffers of every registered event type
+    fixed_step_hooks: Vec<fn(&mut EntityManager)>, // run before every fixed time step
+    snapshot_registry: SnapshotRegistry, // what gets saved in snapshots
+    system_manager: SystemManager,
+    running_systems: Vec<TypeId>, // the systems of the frame in progress, the system manager is moved out meanwhile
+}
+
+// The scheduler hands &EntityManager to other threads, this fails to compile if something stops it from being Sync
+const _: fn() = || {
+    fn assert_sync<T: Sync>() {}
+    assert_sync::<EntityManager>();
+};
+
+impl Default for EntityManager {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl EntityManager {
-    pub fn new(game_manager: Rc<RefCell<GameManager>>) -> Self {
-        EntityManager {
-            next_id: 0,
-            entities: Vec::new(),
+    pub fn new() -> Self {
+        let mut entity_manager = EntityManager {
+            entities: Entities::new(),
             components: HashMap::new(),
-            system_manager: Rc::new(RefCell::new(SystemManager::new())),
-            game_manager,
+            hooks: HashMap::new(),
+            change_tick: 1,
+            last_run_tick: 0,
+            command_queue: CommandQueue::default(),
+            resources: HashMap::new(),
+            non_send_resources: NonSendResources::new(),
+            event_updaters: Vec::new(),
+            fixed_step_hooks: Vec::new(),
+            snapshot_registry: SnapshotRegistry::default(),
+            system_manager: SystemManager::new(),
+            running_systems: Vec::new(),
+        };
+        entity_manager.insert_resource(Time::new());
+        entity_manager.register_hierarchy_hooks();
+        entity_manager.register_name_hooks();
+        entity_manager.register_component::<Parent>("Parent");
+        entity_manager.register_component::<Children>("Children");
+        entity_manager.register_component::<Name>("Name");
+        entity_manager
+    }
+
+    pub fn create_entity(&mut self) -> Entity {
+        let entity = self.reserve_entity();
+        self.spawn_reserved(&entity);
+        entity
+    }
+
+    /// Hands out a handle without bringing the entity to life yet, used by `Commands::spawn`
+    pub fn reserve_entity(&self) -> Entity {
+        self.entities.reserve()
+    }
+
+    /// Brings a reserved entity to life, does nothing if the handle was never reserved
+    pub fn spawn_reserved(&mut self, entity: &Entity) {
+        self.entities.spawn_reserved(entity);
+    }
+
+    /// Destroys an entity and drops all of its components, running their on_remove hooks first.
+    /// Returns false if the handle is stale (the entity was already despawned).
+    pub fn despawn(&mut self, entity: &Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+
+        let hooked_types: Vec<TypeId> = self
+            .components
+            .iter()
+            .filter(|(type_id, storage)| storage.get().contains(entity.id) && self.hooks.contains_key(type_id))
+            .map(|(type_id, _)| *type_id)
+            .collect();
+        for type_id in hooked_types {
+            // An earlier hook may have removed this component already
+            if !self.components.get(&type_id).is_some_and(|storage| storage.get().contains(entity.id)) {
+                continue;
+            }
+            self.run_hooks(type_id, *entity, |hooks| &hooks.on_remove);
+        }
+        if !self.is_alive(entity) {
+            return false; // A hook already despawned it
         }
+
+        for storage in self.components.values_mut() {
+            storage.get_mut().remove_entity(entity.id);
+        }
+        self.components.retain(|_, storage| !storage.get_mut().is_empty());
+
+        self.entities.free(entity);
+        true
     }
 
-    pub fn get_game_manager(&self) -> Rc<RefCell<GameManager>> {
-        Rc::clone(&self.game_manager)
+    /// Checks that the handle still refers to a living entity and not a recycled index
+    pub fn is_alive(&self, entity: &Entity) -> bool {
+        self.entities.is_alive(entity)
     }
 
-    pub fn create_entity(&mut self) -> Entity {
-        let entity = Entity { id: self.next_id };
-        self.next_id += 1;
-        self.entities.push(entity.clone());
+    /// Returns the number of living entities
+    pub fn entity_count(&self) -> usize {
+        self.entities.count()
+    }
+
+    pub fn entities(&self) -> &Entities {
+        &self.entities
+    }
+
+    // Drops every entity and component without running hooks, used when loading a snapshot
+    pub(crate) fn clear_entities(&mut self) {
+        self.components.clear();
+        self.entities = Entities::new();
+        if let Some(index) = self.resource_mut::<NameIndex>() {
+            index.clear();
+        }
+    }
+
+    // Brings back entities with exactly these handles, the world must have just been cleared
+    pub(crate) fn restore_entities(&mut self, entities: &[Entity]) {
+        self.entities.restore(entities);
+    }
+
+    pub(crate) fn snapshot_registry(&self) -> &SnapshotRegistry {
+        &self.snapshot_registry
+    }
+
+    pub(crate) fn snapshot_registry_mut(&mut self) -> &mut SnapshotRegistry {
+        &mut self.snapshot_registry
+    }
+
+    /// Creates an entity and adds every component in the bundle to it
+    pub fn spawn<B: Bundle>(&mut self, bundle: B) -> Entity {
+        let entity = self.create_entity();
+        bundle.insert_into(self, &entity);
         entity
     }
 
+    // Structural changes below all follow the same rules: stale handles are ignored, adding a component
+    // the entity already has replaces it, and a component storage is dropped once its last component is removed
+    // so queries keep short-circuiting on types nobody has. Systems pick the change up on their next query.
+    // on_add hooks run right after a component is added, on_remove hooks right before it is removed while it
+    // can still be read. Replacing a component counts as removing the old one and adding the new one.
+
+    /// Adds a component to an entity, replacing the old one if it already had a component of this type
     pub fn add_component<T: Component + 'static>(&mut self, entity: &Entity, component: T) {
-        let components = self
+        if !self.is_alive(entity) {
+            return;
+        }
+        let type_id = TypeId::of::<T>();
+        if self.has_component::<T>(entity) {
+            self.run_hooks(type_id, *entity, |hooks| &hooks.on_remove);
+            if !self.is_alive(entity) {
+                return;
+            }
+        }
+
+        let change_tick = self.change_tick;
+        let storage = self
             .components
-            .entry(TypeId::of::<T>())
-            .or_insert_with(HashMap::new);
-        components.insert(entity.id, Box::new(component));
+            .entry(type_id)
+            .or_insert_with(|| SystemCell::new(Box::new(SparseSet::<T>::new())));
+        if let Some(storage) = storage.get_mut().as_any_mut().downcast_mut::<SparseSet<T>>() {
+            storage.insert(entity.id, component, change_tick);
+        }
+
+        self.run_hooks(type_id, *entity, |hooks| &hooks.on_add);
+    }
+
+    /// Adds several components at once, e.g. `add_components(&entity, (PlayerData::new(), CameraData::new()))`
+    pub fn add_components<B: Bundle>(&mut self, entity: &Entity, bundle: B) {
+        if self.is_alive(entity) {
+            bundle.insert_into(self, entity);
+        }
+    }
+
+    /// Removes a component from an entity, returns whether it had one
+    pub fn remove_component<T: Component + 'static>(&mut self, entity: &Entity) -> bool {
+        self.take_component::<T>(entity).is_some()
+    }
+
+    /// Removes a component from an entity and hands it back
+    pub fn take_component<T: Component + 'static>(&mut self, entity: &Entity) -> Option<T> {
+        if !self.has_component::<T>(entity) {
+            return None;
+        }
+        self.run_hooks(TypeId::of::<T>(), *entity, |hooks| &hooks.on_remove);
+        if !self.is_alive(entity) {
+            return None;
+        }
+
+        let component = self.storage_mut::<T>()?.remove(entity.id)?;
+        if self.storage::<T>().is_some_and(|storage| storage.values().is_empty()) {
+            self.components.remove(&TypeId::of::<T>());
+        }
+        Some(component)
+    }
+
+    pub fn has_component<T: Component + 'static>(&self, entity: &Entity) -> bool {
+        self.is_alive(entity)
+            && self
+                .components
+                .get(&TypeId::of::<T>())
+                .is_some_and(|storage| storage.get().contains(entity.id))
     }
 
     pub fn get_component<T: Component + 'static>(&self, entity: &Entity) -> Option<&T> {
+        if !self.is_alive(entity) {
+            return None;
+        }
+        self.storage::<T>().and_then(|storage| storage.get(entity.id))
+    }
+
+    /// Borrows a component mutably, writing through it marks it as changed for the Changed filter
+    pub fn get_component_mut<T: Component + 'static>(&mut self, entity: &Entity) -> Option<Mut<'_, T>> {
+        assert_mutable::<T>();
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let change_tick = self.change_tick;
+        self.storage_mut::<T>().and_then(|storage| storage.get_mut(entity.id, change_tick))
+    }
+
+    /// Mutable access through a shared borrow, used by SystemContext.
+    /// # Safety
+    /// The caller must have write access to `T` and no other borrow of this component may be alive
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn get_component_unchecked_mut<T: Component + 'static>(
+        &self,
+        entity: &Entity,
+        change_tick: u32,
+    ) -> Option<Mut<'_, T>> {
+        assert_mutable::<T>();
+        if !self.is_alive(entity) {
+            return None;
+        }
+        let storage = self.components.get(&TypeId::of::<T>())?.as_ptr();
+        (*storage)
+            .as_any_mut()
+            .downcast_mut::<SparseSet<T>>()?
+            .get_mut(entity.id, change_tick)
+    }
+
+    /// Registers a callback run whenever a `T` is added to an entity, e.g. to set up components that depend on it
+    pub fn on_add<T: Component + 'static>(&mut self, hook: impl Fn(&mut EntityManager, Entity) + Send + Sync + 'static) {
+        self.hooks.entry(TypeId::of::<T>()).or_default().on_add.push(Arc::new(hook));
+    }
+
+    /// Registers a callback run whenever a `T` is about to be removed from an entity, including on despawn
+    pub fn on_remove<T: Component + 'static>(&mut self, hook: impl Fn(&mut EntityManager, Entity) + Send + Sync + 'static) {
+        self.hooks.entry(TypeId::of::<T>()).or_default().on_remove.push(Arc::new(hook));
+    }
+
+    // Runs the chosen hooks of one component type, they get the whole entity manager so they are cloned out first
+    fn run_hooks(&mut self, type_id: TypeId, entity: Entity, which: fn(&ComponentHooks) -> &Vec<ComponentHook>) {
+        let hooks = match self.hooks.get(&type_id) {
+            Some(hooks) if !which(hooks).is_empty() => which(hooks).clone(),
+            _ => return,
+        };
+        for hook in hooks {
+            hook(self, entity);
+        }
+    }
+
+    /// The current change tick, every system run gets a new one
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    /// The tick the last frame started at, outside of systems anything added or changed after it
+    /// counts for Added and Changed. Systems get their own from SystemContext.
+    pub fn last_run_tick(&self) -> u32 {
+        self.last_run_tick
+    }
+
+    // Called by the system manager for every system in a batch, returns the tick the system runs at
+    pub(crate) fn next_change_tick(&mut self) -> u32 {
+        self.change_tick = self.change_tick.wrapping_add(1);
+        self.change_tick
+    }
+
+    pub fn query_entities<T: Component + 'static>(&self) -> Vec<Entity> {
+        match self.components.get(&TypeId::of::<T>()) {
+            Some(storage) => storage
+                .get()
+                .ids()
+                .iter()
+                .filter_map(|id| self.entities.entity_from_id(*id))
+                .collect(),
+            None => Vec::new(),
+        }
+    }
+
+    // Looks up and downcasts the storage for one component type
+    fn storage<T: Component + 'static>(&self) -> Option<&SparseSet<T>> {
         self.components
             .get(&TypeId::of::<T>())
-            .and_then(|components| components.get(&entity.id))
-            .and_then(|component| component.downcast_ref::<T>())
+            .and_then(|storage| storage.get().as_any().downcast_ref::<SparseSet<T>>())
     }
 
-    pub fn get_component_mut<T: Component + 'static>(&mut self, entity: &Entity) -> Option<&mut T> {
+    fn storage_mut<T: Component + 'static>(&mut self) -> Option<&mut SparseSet<T>> {
         self.components
             .get_mut(&TypeId::of::<T>())
-            .and_then(|components| components.get_mut(&entity.id))
-            .and_then(|component| component.downcast_mut::<T>())
+            .and_then(|storage| storage.get_mut().as_any_mut().downcast_mut::<SparseSet<T>>())
     }
 
-    pub fn query_entities<T: Component + 'static>(&self) -> Vec<&Entity> {
-        if let Some(components) = self.components.get(&TypeId::of::<T>()) {
-            components
-                .keys()
-                .filter_map(|id| self.entities.iter().find(|entity| entity.id == *id))
-                .collect()
-        } else {
-            Vec::new()
+    /// Iterates every entity that has the queried components, e.g.
+    /// `query::<(Entity, &mut Transform, Option<&RenderData>)>()`
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    /// Same as `query` but only matches entities that pass the filter, e.g. `With<PlayerData>`
+    pub fn query_filtered<Q: Query, F: QueryFilter>(&mut self) -> QueryIter<'_, Q> {
+        // Safety: the iterator holds on to the &mut borrow
+        unsafe { self.query_unchecked::<Q, F>(self.change_tick, self.last_run_tick) }
+    }
+
+    /// Read-only version of `query` that only needs a shared borrow of the entity manager
+    pub fn query_ref<Q: ReadOnlyQuery>(&self) -> QueryIter<'_, Q> {
+        self.query_ref_filtered::<Q, ()>()
+    }
+
+    pub fn query_ref_filtered<Q: ReadOnlyQuery, F: QueryFilter>(&self) -> QueryIter<'_, Q> {
+        // Safety: read-only queries never write through a shared borrow
+        unsafe { self.query_unchecked::<Q, F>(self.change_tick, self.last_run_tick) }
+    }
+
+    /// Runs a query through a shared borrow, used by `query`, `query_ref` and SystemContext.
+    /// # Safety
+    /// The caller must have access to every component the query borrows mutably, no other borrow of those
+    /// components may be alive while the iterator is
+    pub(crate) unsafe fn query_unchecked<Q: Query, F: QueryFilter>(
+        &self,
+        change_tick: u32,
+        last_run_tick: u32,
+    ) -> QueryIter<'_, Q> {
+        let entities = self.matching_entities::<Q, F>(last_run_tick);
+        let state = Q::init(&self.components, change_tick);
+        QueryIter::new(entities, state)
+    }
+
+    // Checks the query borrows for conflicts and collects the entities that match it,
+    // walking the packed ids of the smallest required storage instead of every entity
+    fn matching_entities<Q: Query, F: QueryFilter>(&self, last_run_tick: u32) -> Vec<Entity> {
+        Q::access(&mut Access::default());
+
+        let mut required = Vec::new();
+        Q::required(&mut required);
+
+        let mut storages: Vec<&dyn ComponentStorage> = Vec::with_capacity(required.len());
+        for type_id in &required {
+            match self.components.get(type_id) {
+                Some(storage) => storages.push(storage.get()),
+                None => return Vec::new(), // Nobody has this component yet
+            }
         }
+
+        let matches = |id: &u32| {
+            storages.iter().all(|storage| storage.contains(*id))
+                && F::matches(&self.components, *id, last_run_tick)
+        };
+
+        match storages.iter().min_by_key(|storage| storage.len()) {
+            Some(smallest) => smallest
+                .ids()
+                .iter()
+                .filter(|id| matches(id))
+                .filter_map(|id| self.entities.entity_from_id(*id))
+                .collect(),
+            None => (0..self.entities.id_bound())
+                .filter(matches)
+                .filter_map(|id| self.entities.entity_from_id(id))
+                .collect(),
+        }
+    }
+
+    // Resources are global singletons like the world map or the event queues,
+    // there is at most one of each type and they don't belong to any entity.
+    // Resources that have to stay on the main thread, like the input handler or the window, are non-send resources.
+
+    /// Stores a resource, handing back the old one if there already was one of this type
+    pub fn insert_resource<T: Send + Sync + 'static>(&mut self, resource: T) -> Option<T> {
+        self.resources
+            .insert(TypeId::of::<T>(), SystemCell::new(Box::new(resource)))
+            .and_then(|old| old.into_inner().downcast::<T>().ok())
+            .map(|old| *old)
+    }
+
+    pub fn remove_resource<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.resources
+            .remove(&TypeId::of::<T>())
+            .and_then(|resource| resource.into_inner().downcast::<T>().ok())
+            .map(|resource| *resource)
     }
 
-    pub fn add_system(&mut self, system: Rc<RefCell<dyn System>>) {
-        self.system_manager.borrow_mut().add_system(system);
+    pub fn resource<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|resource| resource.get().downcast_ref::<T>())
+    }
+
+    pub fn resource_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.resources
+            .get_mut(&TypeId::of::<T>())
+            .and_then(|resource| resource.get_mut().downcast_mut::<T>())
+    }
+
+    /// Mutable access through a shared borrow, used by SystemContext.
+    /// # Safety
+    /// The caller must have write access to the resource and no other borrow of it may be alive
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn resource_unchecked_mut<T: Send + Sync + 'static>(&self) -> Option<&mut T> {
+        self.resources
+            .get(&TypeId::of::<T>())
+            .and_then(|resource| (*resource.as_ptr()).downcast_mut::<T>())
     }
 
-    pub fn register_entity_to_system<T: System + 'static>(&mut self, entity: &Entity) {
-        self.system_manager.borrow_mut().register_entity_to_system::<T>(entity.id);
+    pub fn has_resource<T: Send + Sync + 'static>(&self) -> bool {
+        self.resources.contains_key(&TypeId::of::<T>())
+    }
+
+    /// Stores a resource that can't leave the main thread. Only systems declaring `main_thread` can use it.
+    pub fn insert_non_send_resource<T: 'static>(&mut self, resource: T) -> Option<T> {
+        self.non_send_resources.insert(resource)
+    }
+
+    pub fn remove_non_send_resource<T: 'static>(&mut self) -> Option<T> {
+        self.non_send_resources.remove()
+    }
+
+    /// Panics when called from any thread but the main thread
+    pub fn non_send_resource<T: 'static>(&self) -> Option<&T> {
+        self.non_send_resources.get()
+    }
+
+    pub fn non_send_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.non_send_resources.get_mut()
+    }
+
+    /// # Safety
+    /// The caller must have write access to the resource and no other borrow of it may be alive
+    #[allow(clippy::mut_from_ref)]
+    pub(crate) unsafe fn non_send_resource_unchecked_mut<T: 'static>(&self) -> Option<&mut T> {
+        self.non_send_resources.get_unchecked_mut()
+    }
+
+    pub fn has_non_send_resource<T: 'static>(&self) -> bool {
+        self.non_send_resources.contains::<T>()
+    }
+
+    /// Stores the renderer the render systems draw with, as a `Box<dyn Renderer>` non-send resource
+    pub fn insert_renderer<R: Renderer + 'static>(&mut self, renderer: R) -> Option<Box<dyn Renderer>> {
+        self.insert_non_send_resource::<Box<dyn Renderer>>(Box::new(renderer))
+    }
+
+    pub fn renderer(&self) -> Option<&dyn Renderer> {
+        self.non_send_resource::<Box<dyn Renderer>>().map(|renderer| renderer.as_ref())
+    }
+
+    /// Registers an event type, its queue is stored as an `Events<T>` resource and swapped once per frame
+    pub fn add_event<T: Send + Sync + 'static>(&mut self) {
+        if self.has_resource::<Events<T>>() {
+            return;
+        }
+        self.insert_resource(Events::<T>::default());
+        self.event_updaters.push(|entity_manager| {
+            if let Some(events) = entity_manager.resource_mut::<Events<T>>() {
+                events.update();
+            }
+        });
+    }
+
+    /// Returns a writer for a registered event type
+    pub fn event_writer<T: Send + Sync + 'static>(&mut self) -> Option<EventWriter<'_, T>> {
+        self.resource_mut::<Events<T>>().map(EventWriter::new)
+    }
+
+    /// Sends a single event, returns false if the event type was never registered
+    pub fn send_event<T: Send + Sync + 'static>(&mut self, event: T) -> bool {
+        match self.event_writer::<T>() {
+            Some(mut writer) => {
+                writer.send(event);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The queue of a registered event type, pass it to an `EventReader` to read it
+    pub fn events<T: Send + Sync + 'static>(&self) -> Option<&Events<T>> {
+        self.resource::<Events<T>>()
+    }
+
+    // Swaps the event buffers so events from last frame expire
+    fn update_events(&mut self) {
+        for updater in self.event_updaters.clone() {
+            updater(self);
+        }
     }
 
-    pub fn update(&mut self) {
-        let system_manager = Rc::clone(&self.system_manager);
-        
-        // Borrow the system manager mutably
-        system_manager.borrow_mut().update_systems(self);
+    /// Runs the hook before every fixed time step, ahead of the Simulation stage
+    pub fn on_fixed_step(&mut self, hook: fn(&mut EntityManager)) {
+        self.fixed_step_hooks.push(hook);
+    }
+
+    pub(crate) fn run_fixed_step_hooks(&mut self) {
+        for hook in self.fixed_step_hooks.clone() {
+            hook(self);
+        }
+    }
+
+    /// Queues structural changes to be applied at the next sync point
+    pub fn commands(&mut self) -> Commands<'_> {
+        Commands::new(&self.entities, &mut self.command_queue)
+    }
+
+    // Adds the commands a system queued to the end of the queue, in the order the systems are scheduled
+    pub(crate) fn queue_commands(&mut self, commands: &mut CommandQueue) {
+        self.command_queue.append(commands);
+    }
+
+    /// Drops every queued command without applying it, entities reserved by queued spawns are freed again
+    pub fn discard_commands(&mut self) {
+        for entity in self.command_queue.discard() {
+            self.entities.release(&entity);
+        }
+    }
+
+    /// Applies every queued command in the order it was queued. Commands queued by other commands are applied too.
+    pub fn apply_commands(&mut self) {
+        while !self.command_queue.is_empty() {
+            for command in self.command_queue.take() {
+                command(self);
+            }
+        }
+    }
+
+    pub fn add_system<S: System>(&mut self, system: S) {
+        self.system_manager.add_system(Box::new(system));
+    }
+
+    pub(crate) fn add_boxed_system(&mut self, system: Box<dyn System>) {
+        self.system_manager.add_system(system);
+    }
+
+    // Also sees the systems of the frame in progress, so a command adding a system doesn't add it twice
+    pub(crate) fn has_system_type(&self, type_id: TypeId) -> bool {
+        self.running_systems.contains(&type_id) || self.system_manager.has_system(type_id)
+    }
+
+    /// Resolves the system order and lists it stage by stage, fails if the ordering constraints contain a cycle
+    pub fn describe_schedule(&mut self) -> Result<String, EngineError> {
+        self.system_manager.describe_schedule().map_err(EngineError::Schedule)
+    }
+
+    /// What happens when a system without its own policy returns an error, Stop unless changed
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.system_manager.set_error_policy(policy);
+    }
+
+    /// What happens when the system `S` returns an error
+    pub fn set_system_error_policy<S: System>(&mut self, policy: ErrorPolicy) {
+        self.system_manager.set_system_error_policy(TypeId::of::<S>(), policy);
+    }
+
+    /// Runs one frame, timed with the real clock. Returns the first error of a system under the Stop policy,
+    /// the rest of the frame still runs so the world is left in a consistent state.
+    pub fn update(&mut self) -> Result<(), EngineError> {
+        let delta = self.resource_mut::<Time>().map(Time::measure).unwrap_or(0.0);
+        self.update_with_delta(delta)
+    }
+
+    /// Runs one frame that took `delta` seconds, the fixed stages run as many steps as fit into it.
+    /// Same as `update` otherwise, handy when the frame time has to be the same every run.
+    pub fn update_with_delta(&mut self, delta: f32) -> Result<(), EngineError> {
+        // Without a Time resource every frame is one step
+        let fixed_steps = self.resource_mut::<Time>().map(|time| time.advance(delta)).unwrap_or(1);
+
+        // Outside of systems Added and Changed look at everything since the last frame started
+        self.last_run_tick = self.change_tick;
+
+        // The systems need the entity manager while they run, so the system manager is moved out for the frame
+        let mut system_manager = std::mem::take(&mut self.system_manager);
+        self.running_systems = system_manager.system_types();
+        let result = system_manager.update_systems(self, fixed_steps);
+        self.running_systems.clear();
+
+        // Systems added by commands during the frame went to the stand-in
+        let added = std::mem::replace(&mut self.system_manager, system_manager);
+        self.system_manager.append(added);
+
+        // Anything queued outside of a system still gets applied once per frame
+        self.apply_commands();
+
+        // End of frame, events sent this frame stay readable through the next one
+        self.update_events();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Health(u32);
+    impl Component for Health {}
+
+    #[test]
+    fn despawned_id_is_reused_with_a_new_generation() {
+        let mut entity_manager = EntityManager::new();
+        let old = entity_manager.create_entity();
+        assert!(entity_manager.despawn(&old));
+
+        let new = entity_manager.create_entity();
+        assert_eq!(new.id, old.id);
+        assert_eq!(new.generation, old.generation + 1);
+        assert!(entity_manager.is_alive(&new));
+        assert!(!entity_manager.is_alive(&old));
+    }
+
+    #[test]
+    fn stale_handle_does_not_alias_the_recycled_entity() {
+        let mut entity_manager = EntityManager::new();
+        let old = entity_manager.create_entity();
+        entity_manager.add_component(&old, Health(1));
+        entity_manager.despawn(&old);
+
+        let new = entity_manager.create_entity();
+        entity_manager.add_component(&new, Health(2));
+
+        assert!(entity_manager.get_component::<Health>(&old).is_none());
+        assert!(entity_manager.get_component_mut::<Health>(&old).is_none());
+        assert!(!entity_manager.despawn(&old));
+        // The new entity wasn't touched by the stale handle
+        assert!(entity_manager.is_alive(&new));
+        assert_eq!(entity_manager.get_component::<Health>(&new).map(|health| health.0), Some(2));
+    }
+
+    #[test]
+    fn despawning_twice_fails() {
+        let mut entity_manager = EntityManager::new();
+        let entity = entity_manager.create_entity();
+        assert!(entity_manager.despawn(&entity));
+        assert!(!entity_manager.despawn(&entity));
+        assert_eq!(entity_manager.entity_count(), 0);
+    }
+
+    #[test]
+    fn discarded_spawns_give_their_ids_back() {
+        let mut entity_manager = EntityManager::new();
+        let despawned = entity_manager.create_entity();
+        entity_manager.despawn(&despawned);
+
+        // One id comes from the free list, the other one is new
+        let recycled = entity_manager.commands().spawn((Health(1),));
+        let fresh = entity_manager.commands().spawn((Health(2),));
+        entity_manager.discard_commands();
+        entity_manager.apply_commands();
+        assert!(!entity_manager.is_alive(&recycled));
+        assert!(!entity_manager.is_alive(&fresh));
+
+        let mut ids = vec![entity_manager.create_entity().id, entity_manager.create_entity().id];
+        ids.sort();
+        assert_eq!(ids, [recycled.id, fresh.id]);
+        // Nothing was leaked, the next entity gets a brand new id
+        assert_eq!(entity_manager.create_entity().id, fresh.id + 1);
+    }
+
+    #[test]
+    fn taking_a_component_hands_it_back_and_drops_the_empty_storage() {
+        let mut entity_manager = EntityManager::new();
+        let first = entity_manager.spawn((Health(1),));
+        let second = entity_manager.spawn((Health(2),));
+
+        assert_eq!(entity_manager.take_component::<Health>(&first).map(|health| health.0), Some(1));
+        assert!(!entity_manager.has_component::<Health>(&first));
+        assert!(entity_manager.components.contains_key(&TypeId::of::<Health>()));
+
+        assert!(entity_manager.remove_component::<Health>(&second));
+        assert!(!entity_manager.components.contains_key(&TypeId::of::<Health>()));
+        // Nothing left to remove
+        assert!(!entity_manager.remove_component::<Health>(&second));
+        assert!(entity_manager.take_component::<Health>(&second).is_none());
+    }
+
+    #[test]
+    fn on_remove_hooks_run_while_the_component_can_still_be_read() {
+        let mut entity_manager = EntityManager::new();
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let seen = removed.clone();
+        entity_manager.on_remove::<Health>(move |entity_manager, entity| {
+            let health = entity_manager.get_component::<Health>(&entity).map(|health| health.0);
+            seen.lock().unwrap().push(health);
+        });
+
+        let taken = entity_manager.spawn((Health(1),));
+        let despawned = entity_manager.spawn((Health(2),));
+        let replaced = entity_manager.spawn((Health(3),));
+        entity_manager.take_component::<Health>(&taken);
+        entity_manager.despawn(&despawned);
+        entity_manager.add_component(&replaced, Health(4));
+
+        assert_eq!(*removed.lock().unwrap(), [Some(1), Some(2), Some(3)]);
     }
 }
@@ -0,0 +1,148 @@
+// src/ecs/event.rs
+// Description:
+// This module contains typed event channels so systems can talk to each other, e.g. the collision system sends
+// a CollisionEvent and any other system can read it. Events<T> is stored as a resource and is double buffered:
+// an event stays readable for the frame it was sent in and the frame after, then it is dropped.
+// That way a reader sees every event once no matter if it runs before or after the writer.
+
+use std::marker::PhantomData;
+
+struct EventInstance<T> {
+    id: usize,
+    event: T,
+}
+
+/// The queue for one event type, register it with `EntityManager::add_event::<T>()`
+pub struct Events<T> {
+    previous: Vec<EventInstance<T>>, // sent last frame
+    current: Vec<EventInstance<T>>,  // sent this frame
+    event_count: usize,              // id the next event gets
+}
+
+impl<T> Default for Events<T> {
+    fn default() -> Self {
+        Events {
+            previous: Vec::new(),
+            current: Vec::new(),
+            event_count: 0,
+        }
+    }
+}
+
+impl<T> Events<T> {
+    pub fn send(&mut self, event: T) {
+        self.current.push(EventInstance { id: self.event_count, event });
+        self.event_count += 1;
+    }
+
+    /// Swaps the buffers, called once per frame by the entity manager. Events from last frame are dropped.
+    pub fn update(&mut self) {
+        self.previous = std::mem::take(&mut self.current);
+    }
+
+    /// Number of events that can still be read
+    pub fn len(&self) -> usize {
+        self.previous.len() + self.current.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // Every event still buffered with an id of at least `first_id`
+    fn iter_from(&self, first_id: usize) -> impl Iterator<Item = &T> {
+        self.previous
+            .iter()
+            .chain(self.current.iter())
+            .filter(move |instance| instance.id >= first_id)
+            .map(|instance| &instance.event)
+    }
+}
+
+/// Sends events of one type, get one from `EntityManager::event_writer`
+pub struct EventWriter<'a, T> {
+    events: &'a mut Events<T>,
+}
+
+impl<'a, T> EventWriter<'a, T> {
+    pub fn new(events: &'a mut Events<T>) -> Self {
+        EventWriter { events }
+    }
+
+    pub fn send(&mut self, event: T) {
+        self.events.send(event);
+    }
+}
+
+/// Reads events of one type. Each reader remembers how far it has read, so a system keeps its reader
+/// as a field and gets every event exactly once.
+pub struct EventReader<T> {
+    last_read: usize,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> Default for EventReader<T> {
+    fn default() -> Self {
+        EventReader {
+            last_read: 0,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T> EventReader<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns every event sent since the last call
+    pub fn read<'a>(&mut self, events: &'a Events<T>) -> impl Iterator<Item = &'a T> {
+        let first_id = self.last_read;
+        self.last_read = events.event_count;
+        events.iter_from(first_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn events_live_for_the_frame_they_were_sent_and_the_next() {
+        let mut events = Events::default();
+        events.send(1);
+        assert_eq!(events.iter_from(0).copied().collect::<Vec<_>>(), vec![1]);
+
+        events.update();
+        events.send(2);
+        assert_eq!(events.iter_from(0).copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        events.update();
+        assert_eq!(events.iter_from(0).copied().collect::<Vec<_>>(), vec![2]);
+
+        events.update();
+        assert!(events.is_empty());
+    }
+
+    #[test]
+    fn readers_each_see_every_event_once() {
+        let mut events = Events::default();
+        let mut early = EventReader::new();
+        let mut late = EventReader::new();
+
+        events.send(1);
+        assert_eq!(early.read(&events).copied().collect::<Vec<_>>(), vec![1]);
+        events.send(2);
+        assert_eq!(early.read(&events).copied().collect::<Vec<_>>(), vec![2]);
+
+        // The second reader only runs after the frame swap and still gets both
+        events.update();
+        assert_eq!(late.read(&events).copied().collect::<Vec<_>>(), vec![1, 2]);
+
+        events.send(3);
+        assert_eq!(early.read(&events).copied().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(late.read(&events).copied().collect::<Vec<_>>(), vec![3]);
+        assert_eq!(early.read(&events).count(), 0);
+        assert_eq!(late.read(&events).count(), 0);
+    }
+}
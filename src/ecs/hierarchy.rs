@@ -0,0 +1,211 @@
+// src/ecs/hierarchy.rs
+// Description:
+// This module contains the entity manager side of the parent/child hierarchy: attaching and detaching children
+// and despawning whole subtrees. Parent and Children are kept in sync by component hooks, so removing either one
+// directly or despawning an entity leaves no dangling links behind.
+
+use crate::ecs::component::hierarchy::{Children, Parent};
+use crate::ecs::component::transform_data::{GlobalTransform, PreviousTransform, Transform};
+use crate::ecs::entity_manager::{Entity, EntityManager};
+
+impl EntityManager {
+    /// Attaches `child` to `parent`, detaching it from its old parent first. From now on the child's Transform
+    /// is relative to the parent. Returns false if either entity is dead or the link would create a cycle.
+    pub fn set_parent(&mut self, child: &Entity, parent: &Entity) -> bool {
+        if !self.is_alive(child) || !self.is_alive(parent) || child == parent {
+            return false;
+        }
+        if self.ancestors(parent).contains(child) {
+            return false;
+        }
+
+        self.remove_parent(child);
+        self.add_component(child, Parent(*parent));
+        match self.get_component_mut::<Children>(parent) {
+            Some(mut children) => children.0.push(*child),
+            None => self.add_component(parent, Children(vec![*child])),
+        }
+        true
+    }
+
+    /// Detaches an entity from its parent, its Transform becomes world-space again.
+    /// Returns whether it had a parent.
+    pub fn remove_parent(&mut self, child: &Entity) -> bool {
+        // The on_remove hook takes the child out of the parent's Children
+        self.remove_component::<Parent>(child)
+    }
+
+    /// Walks up the hierarchy, closest ancestor first
+    pub fn ancestors(&self, entity: &Entity) -> Vec<Entity> {
+        let mut ancestors = Vec::new();
+        let mut current = *entity;
+        while let Some(parent) = self.get_component::<Parent>(&current) {
+            current = parent.get();
+            ancestors.push(current);
+        }
+        ancestors
+    }
+
+    /// Every entity below this one, depth first
+    pub fn descendants(&self, entity: &Entity) -> Vec<Entity> {
+        let mut descendants = Vec::new();
+        let mut stack = vec![*entity];
+        while let Some(current) = stack.pop() {
+            if let Some(children) = self.get_component::<Children>(&current) {
+                for child in children.iter().rev() {
+                    descendants.push(*child);
+                    stack.push(*child);
+                }
+            }
+        }
+        descendants
+    }
+
+    /// Despawns an entity together with everything attached to it.
+    /// Returns false if the handle is stale.
+    pub fn despawn_recursive(&mut self, entity: &Entity) -> bool {
+        if !self.is_alive(entity) {
+            return false;
+        }
+        // Children go first so every despawn only has to unlink from a parent that is still alive
+        for descendant in self.descendants(entity).into_iter().rev() {
+            self.despawn(&descendant);
+        }
+        self.despawn(entity)
+    }
+
+    // Hooks keeping Parent and Children consistent, giving every Transform a GlobalTransform to write into
+    // (and taking it away again with the Transform) and remembering where every Transform was when a fixed step
+    // starts. Registered by EntityManager::new.
+    pub(crate) fn register_hierarchy_hooks(&mut self) {
+        self.on_remove::<Parent>(|entity_manager, child| {
+            let parent = match entity_manager.get_component::<Parent>(&child) {
+                Some(parent) => parent.get(),
+                None => return,
+            };
+            let now_empty = match entity_manager.get_component_mut::<Children>(&parent) {
+                Some(mut children) => {
+                    children.0.retain(|other| *other != child);
+                    children.is_empty()
+                }
+                None => false,
+            };
+            if now_empty {
+                entity_manager.remove_component::<Children>(&parent);
+            }
+        });
+
+        // Children outliving their parent become roots instead of pointing at a dead entity
+        self.on_remove::<Children>(|entity_manager, parent| {
+            let children = match entity_manager.get_component::<Children>(&parent) {
+                Some(children) => children.0.clone(),
+                None => return,
+            };
+            for child in children {
+                if entity_manager.get_component::<Parent>(&child).is_some_and(|link| link.get() == parent) {
+                    entity_manager.remove_component::<Parent>(&child);
+                }
+            }
+        });
+
+        self.on_add::<Transform>(|entity_manager, entity| {
+            let position = entity_manager
+                .get_component::<Transform>(&entity)
+                .map(|transform| transform.position.clone())
+                .unwrap_or_default();
+            if !entity_manager.has_component::<GlobalTransform>(&entity) {
+                entity_manager.add_component(&entity, GlobalTransform { position: position.clone() });
+            }
+            // A new Transform starts at rest, it shouldn't be drawn sliding in from where an old one was
+            entity_manager.add_component(&entity, PreviousTransform { position });
+        });
+
+        // Without a Transform there's nothing to derive it from, a leftover GlobalTransform would still be drawn
+        self.on_remove::<Transform>(|entity_manager, entity| {
+            entity_manager.remove_component::<GlobalTransform>(&entity);
+        });
+
+        // Same for the interpolation start, a Transform added later starts at rest anyway
+        self.on_remove::<Transform>(|entity_manager, entity| {
+            entity_manager.remove_component::<PreviousTransform>(&entity);
+        });
+
+        self.on_fixed_step(|entity_manager| {
+            for (transform, mut previous) in entity_manager.query::<(&Transform, &mut PreviousTransform)>() {
+                previous.position = transform.position.clone();
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn children_of(entity_manager: &EntityManager, parent: &Entity) -> Vec<Entity> {
+        entity_manager
+            .get_component::<Children>(parent)
+            .map(|children| children.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn set_parent_rejects_cycles() {
+        let mut entity_manager = EntityManager::new();
+        let root = entity_manager.create_entity();
+        let middle = entity_manager.create_entity();
+        let leaf = entity_manager.create_entity();
+        assert!(entity_manager.set_parent(&middle, &root));
+        assert!(entity_manager.set_parent(&leaf, &middle));
+
+        assert!(!entity_manager.set_parent(&root, &root));
+        assert!(!entity_manager.set_parent(&root, &leaf));
+        assert!(!entity_manager.set_parent(&middle, &leaf));
+
+        // The rejected calls left the existing links alone
+        assert!(entity_manager.get_component::<Parent>(&root).is_none());
+        assert_eq!(entity_manager.ancestors(&leaf), vec![middle, root]);
+        assert_eq!(children_of(&entity_manager, &root), vec![middle]);
+        assert_eq!(children_of(&entity_manager, &middle), vec![leaf]);
+    }
+
+    #[test]
+    fn despawn_recursive_removes_every_descendant() {
+        let mut entity_manager = EntityManager::new();
+        let root = entity_manager.create_entity();
+        let child = entity_manager.create_entity();
+        let grandchild = entity_manager.create_entity();
+        let sibling = entity_manager.create_entity();
+        let unrelated = entity_manager.create_entity();
+        entity_manager.set_parent(&child, &root);
+        entity_manager.set_parent(&grandchild, &child);
+        entity_manager.set_parent(&sibling, &root);
+
+        assert!(entity_manager.despawn_recursive(&root));
+        for entity in [root, child, grandchild, sibling] {
+            assert!(!entity_manager.is_alive(&entity));
+        }
+        assert!(entity_manager.is_alive(&unrelated));
+        assert!(!entity_manager.despawn_recursive(&root));
+    }
+
+    #[test]
+    fn removing_parent_updates_the_old_parents_children() {
+        let mut entity_manager = EntityManager::new();
+        let parent = entity_manager.create_entity();
+        let first = entity_manager.create_entity();
+        let second = entity_manager.create_entity();
+        entity_manager.set_parent(&first, &parent);
+        entity_manager.set_parent(&second, &parent);
+
+        assert!(entity_manager.remove_component::<Parent>(&first));
+        assert_eq!(children_of(&entity_manager, &parent), vec![second]);
+
+        // Moving to another parent unlinks from the old one as well
+        let other = entity_manager.create_entity();
+        entity_manager.set_parent(&second, &other);
+        assert!(!entity_manager.has_component::<Children>(&parent));
+        assert_eq!(children_of(&entity_manager, &other), vec![second]);
+        assert_eq!(entity_manager.get_component::<Parent>(&second).map(|parent| parent.get()), Some(other));
+    }
+}
@@ -3,6 +3,17 @@
 //Description:
 //this module just re-exports the other modules in the ecs module.
 
+pub mod bundle;
+pub mod cell;
+pub mod commands;
 pub mod component;
 pub mod entity_manager;
+pub mod event;
+pub mod hierarchy;
+pub mod names;
+pub mod prefab;
+pub mod query;
+pub mod snapshot;
+pub mod storage;
 pub mod system;
+pub mod time;
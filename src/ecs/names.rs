@@ -0,0 +1,156 @@
+// src/ecs/names.rs
+// Description:
+// This module contains the entity manager side of entity names: the index behind `find_by_name` and the labels
+// used in diagnostics. The index is a resource kept up to date by Name hooks, systems that look up entities by
+// name declare `read_resource::<NameIndex>()`.
+
+use crate::ecs::component::name::Name;
+use crate::ecs::entity_manager::{Entity, EntityManager};
+use std::collections::HashMap;
+
+/// Every named entity by name, in the order the names were added
+#[derive(Default)]
+pub struct NameIndex {
+    entities: HashMap<String, Vec<Entity>>,
+}
+
+impl NameIndex {
+    /// The first entity that got this name
+    pub fn get(&self, name: &str) -> Option<Entity> {
+        self.entities.get(name).and_then(|entities| entities.first().copied())
+    }
+
+    /// Every entity with this name
+    pub fn get_all(&self, name: &str) -> &[Entity] {
+        self.entities.get(name).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.entities.clear();
+    }
+
+    fn insert(&mut self, name: &str, entity: Entity) {
+        self.entities.entry(name.to_string()).or_default().push(entity);
+    }
+
+    fn remove(&mut self, name: &str, entity: Entity) {
+        if let Some(entities) = self.entities.get_mut(name) {
+            entities.retain(|other| *other != entity);
+            if entities.is_empty() {
+                self.entities.remove(name);
+            }
+        }
+    }
+}
+
+impl EntityManager {
+    /// The first living entity with this name
+    pub fn find_by_name(&self, name: &str) -> Option<Entity> {
+        self.resource::<NameIndex>().and_then(|index| index.get(name))
+    }
+
+    /// Every living entity with this name
+    pub fn find_all_by_name(&self, name: &str) -> Vec<Entity> {
+        self.resource::<NameIndex>()
+            .map(|index| index.get_all(name).to_vec())
+            .unwrap_or_default()
+    }
+
+    pub fn name_of(&self, entity: &Entity) -> Option<&str> {
+        self.get_component::<Name>(entity).map(Name::as_str)
+    }
+
+    /// How diagnostics refer to an entity: `player (3v0)`, or just `3v0` if it has no name
+    pub fn entity_label(&self, entity: &Entity) -> String {
+        entity_label(self.name_of(entity), entity)
+    }
+
+    // Hooks keeping the NameIndex resource in sync with the Name components. Registered by EntityManager::new.
+    pub(crate) fn register_name_hooks(&mut self) {
+        self.insert_resource(NameIndex::default());
+
+        self.on_add::<Name>(|entity_manager, entity| {
+            let name = match entity_manager.get_component::<Name>(&entity) {
+                Some(name) => name.as_str().to_string(),
+                None => return,
+            };
+            if let Some(index) = entity_manager.resource_mut::<NameIndex>() {
+                index.insert(&name, entity);
+            }
+        });
+
+        self.on_remove::<Name>(|entity_manager, entity| {
+            let name = match entity_manager.get_component::<Name>(&entity) {
+                Some(name) => name.as_str().to_string(),
+                None => return,
+            };
+            if let Some(index) = entity_manager.resource_mut::<NameIndex>() {
+                index.remove(&name, entity);
+            }
+        });
+    }
+}
+
+// Shared with SystemContext::entity_label
+pub(crate) fn entity_label(name: Option<&str>, entity: &Entity) -> String {
+    match name {
+        Some(name) => format!("{} ({})", name, entity),
+        None => entity.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(entity_manager: &mut EntityManager, name: &str) -> Entity {
+        let entity = entity_manager.create_entity();
+        entity_manager.add_component(&entity, Name::new(name));
+        entity
+    }
+
+    #[test]
+    fn despawned_entities_are_not_found() {
+        let mut entity_manager = EntityManager::new();
+        let first = named(&mut entity_manager, "guard");
+        let second = named(&mut entity_manager, "guard");
+
+        entity_manager.despawn(&first);
+        assert_eq!(entity_manager.find_by_name("guard"), Some(second));
+        entity_manager.despawn(&second);
+        assert_eq!(entity_manager.find_by_name("guard"), None);
+        assert!(entity_manager.find_all_by_name("guard").is_empty());
+    }
+
+    #[test]
+    fn adding_a_new_name_renames_the_entity() {
+        let mut entity_manager = EntityManager::new();
+        let entity = named(&mut entity_manager, "old");
+
+        entity_manager.add_component(&entity, Name::new("new"));
+        assert_eq!(entity_manager.find_by_name("old"), None);
+        assert_eq!(entity_manager.find_by_name("new"), Some(entity));
+
+        // Taking the name away and giving it back puts the entity in the index again
+        entity_manager.remove_component::<Name>(&entity);
+        assert_eq!(entity_manager.find_by_name("new"), None);
+        entity_manager.add_component(&entity, Name::new("new"));
+        assert_eq!(entity_manager.find_all_by_name("new"), vec![entity]);
+    }
+
+    #[test]
+    fn names_are_indexed_again_after_loading_a_snapshot() {
+        let mut saved = EntityManager::new();
+        let player = named(&mut saved, "player");
+        let snapshot = saved.snapshot().expect("Failed to save");
+
+        let mut loaded = EntityManager::new();
+        named(&mut loaded, "player");
+        let other = named(&mut loaded, "other");
+        loaded.load_snapshot(snapshot).expect("Failed to load");
+
+        assert_eq!(loaded.find_all_by_name("player"), vec![player]);
+        assert_eq!(loaded.find_by_name("other"), None);
+        assert!(!loaded.is_alive(&other));
+    }
+}
@@ -0,0 +1,314 @@
+// src/ecs/prefab.rs
+// Description:
+// This module contains data-driven prefabs. A prefab is a JSON file naming the components an entity starts with
+// and the systems that have to run for it, e.g. assets/prefabs/player.json:
+//   { "name": "player", "systems": ["PlayerController"], "components": { "PlayerData": { "fov": 60 } } }
+// Component names are the ones registered for snapshots with `register_component`, system names are registered
+// on the PrefabRegistry. Once the registry is inserted with `insert_prefabs`, `spawn_prefab` creates entities from it.
+
+use crate::ecs::entity_manager::{Entity, EntityManager};
+use crate::ecs::system::System;
+use crate::error::EngineError;
+use serde::Deserialize;
+use serde_json::Value;
+use std::any::TypeId;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+/// One prefab file
+#[derive(Clone, Debug, Deserialize)]
+pub struct Prefab {
+    pub name: String,
+    #[serde(default)]
+    pub systems: Vec<String>,
+    #[serde(default)]
+    pub components: BTreeMap<String, Value>,
+}
+
+struct SystemFactory {
+    type_id: TypeId,
+    make: Box<dyn Fn() -> Box<dyn System> + Send + Sync>,
+}
+
+/// Every loaded prefab plus the systems prefab files may name, stored as a resource once inserted
+#[derive(Default)]
+pub struct PrefabRegistry {
+    prefabs: HashMap<String, Prefab>,
+    systems: HashMap<String, SystemFactory>,
+}
+
+impl PrefabRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Lets prefab files name the system `S` as `name`, `make` creates it when a prefab needs it
+    pub fn register_system<S: System>(&mut self, name: &str, make: impl Fn() -> S + Send + Sync + 'static) {
+        self.systems.insert(
+            name.to_string(),
+            SystemFactory {
+                type_id: TypeId::of::<S>(),
+                make: Box::new(move || Box::new(make())),
+            },
+        );
+    }
+
+    /// Adds a prefab, replacing any prefab with the same name
+    pub fn add(&mut self, prefab: Prefab) {
+        self.prefabs.insert(prefab.name.clone(), prefab);
+    }
+
+    /// Reads a single prefab file
+    pub fn load_file(&mut self, path: impl AsRef<Path>) -> Result<(), EngineError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+        let prefab: Prefab =
+            serde_json::from_str(&json).map_err(|error| format!("Failed to parse {}: {}", path.display(), error))?;
+        self.add(prefab);
+        Ok(())
+    }
+
+    /// Reads every .json file in the folder
+    pub fn load_dir(&mut self, path: impl AsRef<Path>) -> Result<(), EngineError> {
+        let path = path.as_ref();
+        let entries = std::fs::read_dir(path).map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+        let mut files = Vec::new();
+        for entry in entries {
+            let file = entry.map_err(|error| format!("Failed to read {}: {}", path.display(), error))?.path();
+            if file.extension().is_some_and(|extension| extension == "json") {
+                files.push(file);
+            }
+        }
+        // Sorted so a name used by two files always resolves the same way
+        files.sort();
+        for file in files {
+            self.load_file(file)?;
+        }
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Prefab> {
+        self.prefabs.get(name)
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.prefabs.keys().map(String::as_str)
+    }
+}
+
+// Merges the overrides into the prefab's values: objects are merged key by key, anything else is replaced
+fn merge(base: &mut Value, overrides: Value) {
+    match (base, overrides) {
+        (Value::Object(base), Value::Object(overrides)) => {
+            for (key, value) in overrides {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overrides) => *base = overrides,
+    }
+}
+
+impl EntityManager {
+    /// Checks every prefab against the registered components and systems, adds the systems the prefabs need
+    /// that aren't running yet and stores the registry as a resource. Nothing is changed if a prefab is invalid.
+    pub fn insert_prefabs(&mut self, registry: PrefabRegistry) -> Result<(), EngineError> {
+        let mut names: Vec<&String> = registry.prefabs.keys().collect();
+        names.sort();
+
+        let mut systems = Vec::new();
+        for name in names {
+            let prefab = &registry.prefabs[name];
+            for (component, value) in &prefab.components {
+                // Parsed only to catch mistakes now rather than when the prefab is first spawned
+                let _ = self
+                    .snapshot_registry()
+                    .parse_component(component, value.clone())
+                    .map_err(|error| format!("Prefab {}: {}", name, error))?;
+            }
+            for system in &prefab.systems {
+                if !registry.systems.contains_key(system) {
+                    return Err(EngineError::Load(format!("Prefab {}: unknown system {}", name, system)));
+                }
+                if !systems.contains(&system) {
+                    systems.push(system);
+                }
+            }
+        }
+
+        for system in systems {
+            let factory = &registry.systems[system];
+            if !self.has_system_type(factory.type_id) {
+                self.add_boxed_system((factory.make)());
+            }
+        }
+        self.insert_resource(registry);
+        Ok(())
+    }
+
+    /// Spawns the named prefab. `overrides` is merged into the prefab's components, e.g.
+    /// `json!({ "Transform": { "position": { "x": 50.0 } } })`, pass `Value::Null` to spawn it as is.
+    pub fn spawn_prefab(&mut self, name: &str, overrides: Value) -> Result<Entity, EngineError> {
+        let registry = self
+            .resource::<PrefabRegistry>()
+            .ok_or_else(|| "No PrefabRegistry has been inserted".to_string())?;
+        let prefab = registry.get(name).ok_or_else(|| format!("Unknown prefab {}", name))?;
+
+        let mut components = Value::Object(prefab.components.clone().into_iter().collect());
+        if !overrides.is_null() {
+            merge(&mut components, overrides);
+        }
+        let Value::Object(components) = components else {
+            return Err(EngineError::Load(format!("Overrides for prefab {} must be an object", name)));
+        };
+
+        // Parse everything first so a bad override doesn't leave a half built entity behind
+        let mut inserts = Vec::with_capacity(components.len());
+        for (component, value) in components {
+            let insert = self
+                .snapshot_registry()
+                .parse_component(&component, value)
+                .map_err(|error| format!("Prefab {}: {}", name, error))?;
+            inserts.push(insert);
+        }
+
+        let entity = self.create_entity();
+        for insert in inserts {
+            insert(self, entity);
+        }
+        Ok(entity)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::component::transform_data::Transform;
+    use crate::ecs::system::access::SystemAccess;
+    use crate::ecs::system::context::SystemContext;
+    use crate::ecs::system::movement_system::MovementSystem;
+    use crate::ecs::system::Stage;
+    use serde_json::json;
+    use std::any::Any;
+
+    fn registry() -> PrefabRegistry {
+        let mut registry = PrefabRegistry::new();
+        registry.register_system("MovementSystem", || MovementSystem);
+        let prefab = json!({
+            "name": "mover",
+            "systems": ["MovementSystem"],
+            "components": { "Transform": { "position": { "x": 1.0, "y": 2.0, "rotation": 0.5 } } }
+        });
+        registry.add(serde_json::from_value(prefab).expect("Failed to parse the test prefab"));
+        registry
+    }
+
+    fn entity_manager() -> EntityManager {
+        let mut entity_manager = EntityManager::new();
+        entity_manager.register_component::<Transform>("Transform");
+        entity_manager
+    }
+
+    fn count_in_schedule(entity_manager: &mut EntityManager, system: &str) -> usize {
+        let schedule = entity_manager.describe_schedule().expect("Failed to build the schedule");
+        schedule.matches(system).count()
+    }
+
+    // Inserts the prefabs and spawns one from a command on its first run
+    struct SpawnFromCommand {
+        done: bool,
+    }
+
+    impl System for SpawnFromCommand {
+        fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+            if !self.done {
+                self.done = true;
+                context.commands().add(|entity_manager| {
+                    entity_manager.insert_prefabs(registry()).expect("Failed to insert the prefabs");
+                    entity_manager.spawn_prefab("mover", Value::Null).expect("Failed to spawn the prefab");
+                });
+            }
+            Ok(())
+        }
+
+        fn stage(&self) -> Stage {
+            Stage::PostPhysics
+        }
+
+        fn access(&self) -> SystemAccess {
+            SystemAccess::new()
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn prefab_spawned_from_a_command_does_not_add_a_running_system_again() {
+        let mut entity_manager = entity_manager();
+        entity_manager.add_system(MovementSystem);
+        entity_manager.add_system(SpawnFromCommand { done: false });
+
+        entity_manager.update_with_delta(0.0).expect("Failed to run the frame");
+        entity_manager.update_with_delta(0.0).expect("Failed to run the frame");
+
+        assert_eq!(entity_manager.query::<&Transform>().count(), 1);
+        assert_eq!(count_in_schedule(&mut entity_manager, "MovementSystem"), 1);
+    }
+
+    #[test]
+    fn overrides_only_replace_the_fields_they_name() {
+        let mut entity_manager = entity_manager();
+        entity_manager.insert_prefabs(registry()).expect("Failed to insert the prefabs");
+
+        let overrides = json!({ "Transform": { "position": { "x": 50.0 } } });
+        let entity = entity_manager.spawn_prefab("mover", overrides).expect("Failed to spawn the prefab");
+        let position = &entity_manager.get_component::<Transform>(&entity).expect("Transform is missing").position;
+        assert_eq!((position.x(), position.y(), position.rotation()), (50.0, 2.0, 0.5));
+    }
+
+    #[test]
+    fn unknown_prefabs_and_components_are_errors() {
+        let mut entity_manager = entity_manager();
+        entity_manager.insert_prefabs(registry()).expect("Failed to insert the prefabs");
+
+        let error = entity_manager.spawn_prefab("ghost", Value::Null).unwrap_err();
+        assert!(matches!(&error, EngineError::Load(message) if message == "Unknown prefab ghost"), "{}", error);
+
+        let error = entity_manager.spawn_prefab("mover", json!({ "Armor": 3 })).unwrap_err();
+        assert!(matches!(&error, EngineError::Load(message) if message.contains("Unknown component Armor")), "{}", error);
+        assert_eq!(entity_manager.entity_count(), 0);
+
+        // Caught when the registry is inserted too, before anything is added
+        let mut broken = registry();
+        broken.add(serde_json::from_value(json!({ "name": "broken", "components": { "Armor": 3 } })).unwrap());
+        let mut fresh = self::entity_manager();
+        let error = fresh.insert_prefabs(broken).unwrap_err();
+        assert!(matches!(&error, EngineError::Load(message) if message.contains("Unknown component Armor")), "{}", error);
+        assert!(!fresh.has_resource::<PrefabRegistry>());
+        assert_eq!(count_in_schedule(&mut fresh, "MovementSystem"), 0);
+    }
+
+    #[test]
+    fn insert_prefabs_adds_every_listed_system_once() {
+        let mut two_movers = registry();
+        two_movers.add(serde_json::from_value(json!({ "name": "other mover", "systems": ["MovementSystem"] })).unwrap());
+
+        let mut entity_manager = entity_manager();
+        entity_manager.insert_prefabs(two_movers).expect("Failed to insert the prefabs");
+        assert_eq!(count_in_schedule(&mut entity_manager, "MovementSystem"), 1);
+
+        // Inserting again, e.g. after loading more prefab files, doesn't add it a second time
+        entity_manager.insert_prefabs(registry()).expect("Failed to insert the prefabs");
+        assert_eq!(count_in_schedule(&mut entity_manager, "MovementSystem"), 1);
+    }
+}
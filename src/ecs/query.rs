@@ -0,0 +1,459 @@
+// src/ecs/query.rs
+// Description:
+// This module contains the typed query API. A query names the components it wants as a tuple such as
+// `(Entity, &mut Transform, Option<&RenderData>)` and yields those borrows for every entity that has them,
+// so systems don't have to fetch and unwrap every component by hand.
+// Filters like `With<PlayerData>` or `Without<CollisionData>` narrow the matches without borrowing anything.
+// `Added<T>` and `Changed<T>` only match components added or written to since the running system last ran.
+// That's why `&mut T` is handed out as a `Mut<T>`, which marks the component as changed on the first write
+// instead of on every mutable borrow.
+
+use crate::ecs::cell::SystemCell;
+use crate::ecs::component::{assert_mutable, Component};
+use crate::ecs::entity_manager::Entity;
+use crate::ecs::storage::{ComponentStorage, Mut, SparseSet};
+use std::any::{type_name, TypeId};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+/// Every component storage by component type, what queries are built from
+pub type ComponentMap = HashMap<TypeId, SystemCell<dyn ComponentStorage>>;
+
+/// Records which component types a query reads and writes, so two borrows of the same type
+/// where one of them is mutable are caught before any reference is handed out.
+/// Types a filter looks at are kept apart, a filter never hands out a reference so it can't conflict.
+#[derive(Default)]
+pub struct Access {
+    reads: Vec<(TypeId, &'static str)>,
+    writes: Vec<(TypeId, &'static str)>,
+    filters: Vec<(TypeId, &'static str)>,
+}
+
+impl Access {
+    pub fn add_read<T: 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if self.writes.iter().any(|(id, _)| *id == type_id) {
+            panic!("Query reads {} while also borrowing it mutably", type_name::<T>());
+        }
+        self.reads.push((type_id, type_name::<T>()));
+    }
+
+    pub fn add_write<T: 'static>(&mut self) {
+        let type_id = TypeId::of::<T>();
+        if self.reads.iter().chain(self.writes.iter()).any(|(id, _)| *id == type_id) {
+            panic!("Query borrows {} mutably while also borrowing it elsewhere", type_name::<T>());
+        }
+        self.writes.push((type_id, type_name::<T>()));
+    }
+
+    pub fn add_filter<T: 'static>(&mut self) {
+        self.filters.push((TypeId::of::<T>(), type_name::<T>()));
+    }
+
+    /// Every type that is read, by the query itself or by its filter
+    pub fn reads(&self) -> impl Iterator<Item = &(TypeId, &'static str)> {
+        self.reads.iter().chain(self.filters.iter())
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = &(TypeId, &'static str)> {
+        self.writes.iter()
+    }
+}
+
+/// Something that can be fetched for a single entity: `Entity`, `&T`, `&mut T`, `Option<&T>`,
+/// `Option<&mut T>` or a tuple of those.
+pub trait Query {
+    type Item<'a>;
+    // Per-query pointers to the storages this query touches, looked up and downcast once instead of per entity
+    type State: Copy;
+
+    fn access(access: &mut Access);
+
+    /// Adds the component types an entity must have to match
+    fn required(required: &mut Vec<TypeId>);
+
+    /// # Safety
+    /// The caller must have access to every storage the query borrows for as long as the state is used,
+    /// either through `&mut EntityManager` or through access the scheduler checked.
+    /// Writes through mutable borrows mark the component as changed at `change_tick`.
+    unsafe fn init(components: &ComponentMap, change_tick: u32) -> Self::State;
+
+    /// # Safety
+    /// The entity must have every required component and the access set must have been checked.
+    unsafe fn fetch<'a>(state: Self::State, entity: Entity) -> Self::Item<'a>;
+}
+
+/// Queries that only read, these can be run through a shared `&EntityManager`
+pub trait ReadOnlyQuery: Query {}
+
+/// Narrows down which entities a query matches without borrowing their components.
+/// `last_run_tick` is the change tick the running system last ran at.
+pub trait QueryFilter {
+    /// Adds the component types the filter looks at
+    fn access(_access: &mut Access) {}
+
+    fn matches(components: &ComponentMap, id: u32, last_run_tick: u32) -> bool;
+}
+
+/// Only match entities that have a `T`
+pub struct With<T>(PhantomData<T>);
+
+/// Only match entities that do not have a `T`
+pub struct Without<T>(PhantomData<T>);
+
+/// Only match entities whose `T` was added since the system last ran
+pub struct Added<T>(PhantomData<T>);
+
+/// Only match entities whose `T` was added or written to since the system last ran
+pub struct Changed<T>(PhantomData<T>);
+
+impl<T: Component + 'static> QueryFilter for With<T> {
+    fn access(access: &mut Access) {
+        access.add_filter::<T>();
+    }
+
+    fn matches(components: &ComponentMap, id: u32, _last_run_tick: u32) -> bool {
+        components
+            .get(&TypeId::of::<T>())
+            .is_some_and(|storage| storage.get().contains(id))
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for Without<T> {
+    fn access(access: &mut Access) {
+        access.add_filter::<T>();
+    }
+
+    fn matches(components: &ComponentMap, id: u32, last_run_tick: u32) -> bool {
+        !With::<T>::matches(components, id, last_run_tick)
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for Added<T> {
+    fn access(access: &mut Access) {
+        access.add_filter::<T>();
+    }
+
+    fn matches(components: &ComponentMap, id: u32, last_run_tick: u32) -> bool {
+        components
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.get().ticks(id))
+            .is_some_and(|ticks| ticks.is_added_after(last_run_tick))
+    }
+}
+
+impl<T: Component + 'static> QueryFilter for Changed<T> {
+    fn access(access: &mut Access) {
+        access.add_filter::<T>();
+    }
+
+    fn matches(components: &ComponentMap, id: u32, last_run_tick: u32) -> bool {
+        components
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.get().ticks(id))
+            .is_some_and(|ticks| ticks.is_changed_after(last_run_tick))
+    }
+}
+
+impl QueryFilter for () {
+    fn matches(_components: &ComponentMap, _id: u32, _last_run_tick: u32) -> bool {
+        true
+    }
+}
+
+impl Query for Entity {
+    type Item<'a> = Entity;
+    type State = ();
+
+    fn access(_access: &mut Access) {}
+
+    fn required(_required: &mut Vec<TypeId>) {}
+
+    unsafe fn init(_components: &ComponentMap, _change_tick: u32) -> Self::State {}
+
+    unsafe fn fetch<'a>(_state: Self::State, entity: Entity) -> Self::Item<'a> {
+        entity
+    }
+}
+
+impl ReadOnlyQuery for Entity {}
+
+impl<T: Component + 'static> Query for &T {
+    type Item<'a> = &'a T;
+    type State = Option<*const SparseSet<T>>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn required(required: &mut Vec<TypeId>) {
+        required.push(TypeId::of::<T>());
+    }
+
+    unsafe fn init(components: &ComponentMap, _change_tick: u32) -> Self::State {
+        components
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| storage.get().as_any().downcast_ref::<SparseSet<T>>())
+            .map(|storage| storage as *const SparseSet<T>)
+    }
+
+    unsafe fn fetch<'a>(state: Self::State, entity: Entity) -> Self::Item<'a> {
+        <Option<&T> as Query>::fetch(state, entity)
+            .unwrap_or_else(|| panic!("Query matched {} without a required {}", entity, type_name::<T>()))
+    }
+}
+
+impl<T: Component + 'static> ReadOnlyQuery for &T {}
+
+impl<T: Component + 'static> Query for &mut T {
+    type Item<'a> = Mut<'a, T>;
+    type State = Option<(*mut SparseSet<T>, u32)>;
+
+    fn access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    fn required(required: &mut Vec<TypeId>) {
+        required.push(TypeId::of::<T>());
+    }
+
+    unsafe fn init(components: &ComponentMap, change_tick: u32) -> Self::State {
+        assert_mutable::<T>();
+        components
+            .get(&TypeId::of::<T>())
+            .and_then(|storage| (*storage.as_ptr()).as_any_mut().downcast_mut::<SparseSet<T>>())
+            .map(|storage| (storage as *mut SparseSet<T>, change_tick))
+    }
+
+    unsafe fn fetch<'a>(state: Self::State, entity: Entity) -> Self::Item<'a> {
+        <Option<&mut T> as Query>::fetch(state, entity)
+            .unwrap_or_else(|| panic!("Query matched {} without a required {}", entity, type_name::<T>()))
+    }
+}
+
+impl<T: Component + 'static> Query for Option<&T> {
+    type Item<'a> = Option<&'a T>;
+    type State = Option<*const SparseSet<T>>;
+
+    fn access(access: &mut Access) {
+        access.add_read::<T>();
+    }
+
+    fn required(_required: &mut Vec<TypeId>) {}
+
+    unsafe fn init(components: &ComponentMap, change_tick: u32) -> Self::State {
+        <&T as Query>::init(components, change_tick)
+    }
+
+    unsafe fn fetch<'a>(state: Self::State, entity: Entity) -> Self::Item<'a> {
+        state.and_then(|storage| (*storage).get(entity.id))
+    }
+}
+
+impl<T: Component + 'static> ReadOnlyQuery for Option<&T> {}
+
+impl<T: Component + 'static> Query for Option<&mut T> {
+    type Item<'a> = Option<Mut<'a, T>>;
+    type State = Option<(*mut SparseSet<T>, u32)>;
+
+    fn access(access: &mut Access) {
+        access.add_write::<T>();
+    }
+
+    fn required(_required: &mut Vec<TypeId>) {}
+
+    unsafe fn init(components: &ComponentMap, change_tick: u32) -> Self::State {
+        <&mut T as Query>::init(components, change_tick)
+    }
+
+    unsafe fn fetch<'a>(state: Self::State, entity: Entity) -> Self::Item<'a> {
+        // Goes through the raw dense pointer so earlier &mut to other entities' components stay valid,
+        // every entity is fetched at most once so the borrows never overlap
+        let (storage, change_tick) = state?;
+        let index = (*storage).dense_index(entity.id)?;
+        Some(Mut::new(
+            &mut *(*storage).dense_ptr().add(index),
+            &mut *(*storage).ticks_ptr().add(index),
+            change_tick,
+        ))
+    }
+}
+
+// Implements Query and QueryFilter for tuples so several components can be fetched or filtered at once
+macro_rules! impl_query_tuple {
+    ($($name:ident),*) => {
+        #[allow(non_snake_case)]
+        impl<$($name: Query),*> Query for ($($name,)*) {
+            type Item<'a> = ($($name::Item<'a>,)*);
+            type State = ($($name::State,)*);
+
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn required(required: &mut Vec<TypeId>) {
+                $($name::required(required);)*
+            }
+
+            unsafe fn init(components: &ComponentMap, change_tick: u32) -> Self::State {
+                ($($name::init(components, change_tick),)*)
+            }
+
+            unsafe fn fetch<'a>(state: Self::State, entity: Entity) -> Self::Item<'a> {
+                let ($($name,)*) = state;
+                ($($name::fetch($name, entity),)*)
+            }
+        }
+
+        impl<$($name: ReadOnlyQuery),*> ReadOnlyQuery for ($($name,)*) {}
+
+        impl<$($name: QueryFilter),*> QueryFilter for ($($name,)*) {
+            fn access(access: &mut Access) {
+                $($name::access(access);)*
+            }
+
+            fn matches(components: &ComponentMap, id: u32, last_run_tick: u32) -> bool {
+                $($name::matches(components, id, last_run_tick))&&*
+            }
+        }
+    };
+}
+
+impl_query_tuple!(A);
+impl_query_tuple!(A, B);
+impl_query_tuple!(A, B, C);
+impl_query_tuple!(A, B, C, D);
+impl_query_tuple!(A, B, C, D, E);
+impl_query_tuple!(A, B, C, D, E, F);
+
+/// Iterator over the results of a query, created by `EntityManager::query` and friends.
+/// The matching entities are worked out up front, each step only fetches the borrows.
+pub struct QueryIter<'a, Q: Query> {
+    entities: std::vec::IntoIter<Entity>,
+    state: Q::State,
+    _marker: PhantomData<&'a mut ComponentMap>,
+}
+
+impl<'a, Q: Query> QueryIter<'a, Q> {
+    pub(crate) fn new(entities: Vec<Entity>, state: Q::State) -> Self {
+        QueryIter {
+            entities: entities.into_iter(),
+            state,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<'a, Q: Query> Iterator for QueryIter<'a, Q> {
+    type Item = Q::Item<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let entity = self.entities.next()?;
+        // Safety: the entity matched every required component and every entity is yielded once
+        Some(unsafe { Q::fetch(self.state, entity) })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.entities.size_hint()
+    }
+}
+
+impl<'a, Q: Query> ExactSizeIterator for QueryIter<'a, Q> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::entity_manager::EntityManager;
+
+    struct Health(u32);
+    impl Component for Health {}
+
+    struct Armor(u32);
+    impl Component for Armor {}
+
+    struct Enemy;
+    impl Component for Enemy {}
+
+    // An entity with health and armor, one with only health and an enemy with only health
+    fn world() -> (EntityManager, [Entity; 3]) {
+        let mut entity_manager = EntityManager::new();
+        let armored = entity_manager.spawn((Health(10), Armor(5)));
+        let unarmored = entity_manager.spawn((Health(20),));
+        let enemy = entity_manager.spawn((Health(30), Enemy));
+        (entity_manager, [armored, unarmored, enemy])
+    }
+
+    #[test]
+    fn tuple_queries_only_match_entities_with_every_component() {
+        let (mut entity_manager, [armored, ..]) = world();
+        let matches: Vec<(Entity, u32, u32)> = entity_manager
+            .query::<(Entity, &Health, &Armor)>()
+            .map(|(entity, health, armor)| (entity, health.0, armor.0))
+            .collect();
+        assert_eq!(matches, [(armored, 10, 5)]);
+    }
+
+    #[test]
+    fn optional_components_are_none_where_missing() {
+        let (mut entity_manager, [armored, unarmored, enemy]) = world();
+        let matches: Vec<(Entity, Option<u32>)> = entity_manager
+            .query::<(Entity, Option<&Armor>)>()
+            .map(|(entity, armor)| (entity, armor.map(|armor| armor.0)))
+            .collect();
+        assert_eq!(matches, [(armored, Some(5)), (unarmored, None), (enemy, None)]);
+
+        for (health, armor) in entity_manager.query::<(&mut Health, Option<&mut Armor>)>() {
+            if let Some(mut armor) = armor {
+                armor.0 += health.0;
+            }
+        }
+        assert_eq!(entity_manager.get_component::<Armor>(&armored).map(|armor| armor.0), Some(15));
+    }
+
+    #[test]
+    fn with_and_without_filter_by_component() {
+        let (mut entity_manager, [armored, unarmored, enemy]) = world();
+        let enemies: Vec<Entity> = entity_manager.query_filtered::<Entity, With<Enemy>>().collect();
+        assert_eq!(enemies, [enemy]);
+        let others: Vec<Entity> = entity_manager.query_filtered::<Entity, (With<Health>, Without<Enemy>)>().collect();
+        assert_eq!(others, [armored, unarmored]);
+    }
+
+    #[test]
+    fn added_only_matches_components_added_since_the_last_frame() {
+        let (mut entity_manager, [armored, unarmored, _]) = world();
+        entity_manager.update_with_delta(0.0).unwrap();
+        // Stands in for a system running, its changes get a newer tick
+        entity_manager.next_change_tick();
+        entity_manager.add_component(&unarmored, Armor(1));
+
+        let added: Vec<Entity> = entity_manager.query_filtered::<Entity, Added<Armor>>().collect();
+        assert_eq!(added, [unarmored]);
+        assert!(!added.contains(&armored));
+    }
+
+    #[test]
+    fn changed_only_matches_components_written_since_the_last_frame() {
+        let (mut entity_manager, [armored, unarmored, enemy]) = world();
+        entity_manager.update_with_delta(0.0).unwrap();
+        entity_manager.next_change_tick();
+
+        if let Some(mut health) = entity_manager.get_component_mut::<Health>(&unarmored) {
+            health.0 -= 1;
+        }
+        // Only read through the mutable borrow, that isn't a change
+        let enemy_health = entity_manager.get_component_mut::<Health>(&enemy).map(|health| health.0);
+        assert_eq!(enemy_health, Some(30));
+
+        let changed: Vec<Entity> = entity_manager.query_filtered::<Entity, Changed<Health>>().collect();
+        assert_eq!(changed, [unarmored]);
+        assert!(!changed.contains(&armored));
+    }
+
+    #[test]
+    #[should_panic(expected = "while also borrowing it mutably")]
+    fn reading_and_writing_the_same_component_panics() {
+        let (mut entity_manager, _) = world();
+        let _ = entity_manager.query::<(&mut Health, &Health)>();
+    }
+}
@@ -0,0 +1,359 @@
+// src/ecs/snapshot.rs
+// Description:
+// This module contains world snapshots, used to save the game to a file and load it again.
+// Only components and resources registered under a stable name are saved, e.g.
+// `entity_manager.register_component::<Transform>("Transform")`. Everything else (the window, GlobalTransform, ...)
+// is left out, hooks run while a snapshot is loaded so derived components are rebuilt on their own.
+// Entities keep their ids and generations, so handles stored in components like Parent stay valid.
+
+use crate::ecs::component::hierarchy::{Children, Parent};
+use crate::ecs::component::Component;
+use crate::ecs::entity_manager::{Entity, EntityManager};
+use crate::error::EngineError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+/// Bumped whenever the layout of a snapshot changes in a way older files can't be loaded with
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+/// Everything registered in the world, as plain JSON values keyed by registered name
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WorldSnapshot {
+    pub version: u32,
+    pub entities: Vec<EntitySnapshot>,
+    pub resources: BTreeMap<String, Value>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct EntitySnapshot {
+    pub entity: Entity,
+    pub components: BTreeMap<String, Value>,
+}
+
+// A parsed value waiting to be put into the world. Everything is parsed before the world is touched,
+// so a broken file can't leave half a world behind.
+pub(crate) type InsertComponent = Box<dyn FnOnce(&mut EntityManager, Entity)>;
+type InsertResource = Box<dyn FnOnce(&mut EntityManager)>;
+
+#[derive(Clone, Copy)]
+struct ComponentRegistration {
+    name: &'static str,
+    save: fn(&EntityManager, Entity) -> Option<Result<Value, serde_json::Error>>,
+    parse: fn(Value) -> Result<InsertComponent, serde_json::Error>,
+}
+
+#[derive(Clone, Copy)]
+struct ResourceRegistration {
+    name: &'static str,
+    save: fn(&EntityManager) -> Option<Result<Value, serde_json::Error>>,
+    parse: fn(Value) -> Result<InsertResource, serde_json::Error>,
+}
+
+/// The components and resources that end up in a snapshot, owned by the entity manager
+#[derive(Clone, Default)]
+pub struct SnapshotRegistry {
+    components: Vec<ComponentRegistration>,
+    resources: Vec<ResourceRegistration>,
+}
+
+impl SnapshotRegistry {
+    // Parses a value saved under a registered component name
+    pub(crate) fn parse_component(&self, name: &str, value: Value) -> Result<InsertComponent, String> {
+        let registration = self
+            .components
+            .iter()
+            .find(|registration| registration.name == name)
+            .ok_or_else(|| format!("Unknown component {}", name))?;
+        (registration.parse)(value).map_err(|error| format!("Failed to load {}: {}", name, error))
+    }
+}
+
+fn save_component<T: Component + Serialize>(entity_manager: &EntityManager, entity: Entity) -> Option<Result<Value, serde_json::Error>> {
+    entity_manager.get_component::<T>(&entity).map(serde_json::to_value)
+}
+
+fn parse_component<T: Component + DeserializeOwned>(value: Value) -> Result<InsertComponent, serde_json::Error> {
+    let component: T = serde_json::from_value(value)?;
+    Ok(Box::new(move |entity_manager, entity| entity_manager.add_component(&entity, component)))
+}
+
+fn save_resource<T: Send + Sync + Serialize + 'static>(entity_manager: &EntityManager) -> Option<Result<Value, serde_json::Error>> {
+    entity_manager.resource::<T>().map(serde_json::to_value)
+}
+
+fn parse_resource<T: Send + Sync + DeserializeOwned + 'static>(value: Value) -> Result<InsertResource, serde_json::Error> {
+    let resource: T = serde_json::from_value(value)?;
+    Ok(Box::new(move |entity_manager| {
+        entity_manager.insert_resource(resource);
+    }))
+}
+
+// Entities are restored with exactly the saved handles, so every id has to be unique and every Parent and
+// Children link has to point at an entity of the snapshot
+fn check_entities(entities: &[EntitySnapshot]) -> Result<(), String> {
+    let mut ids = HashSet::with_capacity(entities.len());
+    for entity_snapshot in entities {
+        if !ids.insert(entity_snapshot.entity.id) {
+            return Err(format!("Entity id {} appears twice in the snapshot", entity_snapshot.entity.id));
+        }
+    }
+
+    let handles: HashSet<Entity> = entities.iter().map(|entity_snapshot| entity_snapshot.entity).collect();
+    for entity_snapshot in entities {
+        let components = &entity_snapshot.components;
+        let mut linked = Vec::new();
+        if let Some(value) = components.get("Parent") {
+            let parent: Parent =
+                serde_json::from_value(value.clone()).map_err(|error| format!("Failed to load Parent: {}", error))?;
+            linked.push(parent.get());
+        }
+        if let Some(value) = components.get("Children") {
+            let children: Children =
+                serde_json::from_value(value.clone()).map_err(|error| format!("Failed to load Children: {}", error))?;
+            linked.extend(children.iter().copied());
+        }
+        if let Some(missing) = linked.into_iter().find(|entity| !handles.contains(entity)) {
+            return Err(format!("{} is linked to {}, which is not in the snapshot", entity_snapshot.entity, missing));
+        }
+    }
+    Ok(())
+}
+
+impl EntityManager {
+    /// Saves `T` under `name` in snapshots. The name ends up in save files, so it must not change once released.
+    pub fn register_component<T: Component + Serialize + DeserializeOwned>(&mut self, name: &'static str) {
+        let registry = self.snapshot_registry_mut();
+        registry.components.retain(|registration| registration.name != name);
+        registry.components.push(ComponentRegistration {
+            name,
+            save: save_component::<T>,
+            parse: parse_component::<T>,
+        });
+    }
+
+    /// Saves the resource `T` under `name` in snapshots
+    pub fn register_resource<T: Send + Sync + Serialize + DeserializeOwned + 'static>(&mut self, name: &'static str) {
+        let registry = self.snapshot_registry_mut();
+        registry.resources.retain(|registration| registration.name != name);
+        registry.resources.push(ResourceRegistration {
+            name,
+            save: save_resource::<T>,
+            parse: parse_resource::<T>,
+        });
+    }
+
+    /// Captures every registered component of every entity and every registered resource
+    pub fn snapshot(&self) -> Result<WorldSnapshot, EngineError> {
+        let registry = self.snapshot_registry();
+
+        let mut entities = Vec::new();
+        for entity in self.entities().iter() {
+            let mut components = BTreeMap::new();
+            for registration in &registry.components {
+                if let Some(value) = (registration.save)(self, entity) {
+                    let value = value.map_err(|error| {
+                        format!("Failed to save {} of {}: {}", registration.name, self.entity_label(&entity), error)
+                    })?;
+                    components.insert(registration.name.to_string(), value);
+                }
+            }
+            entities.push(EntitySnapshot { entity, components });
+        }
+
+        let mut resources = BTreeMap::new();
+        for registration in &registry.resources {
+            if let Some(value) = (registration.save)(self) {
+                let value = value.map_err(|error| format!("Failed to save {}: {}", registration.name, error))?;
+                resources.insert(registration.name.to_string(), value);
+            }
+        }
+
+        Ok(WorldSnapshot { version: SNAPSHOT_VERSION, entities, resources })
+    }
+
+    /// Replaces every entity and registered resource with the ones in the snapshot.
+    /// Resources that were never registered, like the window, are left alone.
+    /// Nothing is changed if the snapshot can't be loaded.
+    pub fn load_snapshot(&mut self, snapshot: WorldSnapshot) -> Result<(), EngineError> {
+        if snapshot.version != SNAPSHOT_VERSION {
+            return Err(EngineError::Load(format!(
+                "Snapshot version {} is not supported, expected {}",
+                snapshot.version, SNAPSHOT_VERSION
+            )));
+        }
+        check_entities(&snapshot.entities)?;
+        let registry = self.snapshot_registry().clone();
+
+        let mut entities = Vec::with_capacity(snapshot.entities.len());
+        for entity_snapshot in snapshot.entities {
+            let mut inserts = Vec::with_capacity(entity_snapshot.components.len());
+            for (name, value) in entity_snapshot.components {
+                inserts.push(registry.parse_component(&name, value)?);
+            }
+            entities.push((entity_snapshot.entity, inserts));
+        }
+
+        let mut resources = Vec::with_capacity(snapshot.resources.len());
+        for (name, value) in snapshot.resources {
+            let registration = registry
+                .resources
+                .iter()
+                .find(|registration| registration.name == name)
+                .ok_or_else(|| format!("Unknown resource {} in snapshot", name))?;
+            resources.push((registration.parse)(value).map_err(|error| format!("Failed to load {}: {}", name, error))?);
+        }
+
+        // Everything parsed, swap the world out. Queued commands were meant for the old world
+        let handles: Vec<Entity> = entities.iter().map(|(entity, _)| *entity).collect();
+        self.discard_commands();
+        self.clear_entities();
+        self.restore_entities(&handles);
+        for (entity, inserts) in entities {
+            for insert in inserts {
+                insert(self, entity);
+            }
+        }
+        for insert in resources {
+            insert(self);
+        }
+        Ok(())
+    }
+
+    /// Writes a snapshot of the world to a JSON file, creating the folder if needed
+    pub fn save_world(&self, path: impl AsRef<Path>) -> Result<(), EngineError> {
+        let path = path.as_ref();
+        let json = serde_json::to_string_pretty(&self.snapshot()?)
+            .map_err(|error| format!("Failed to save the world: {}", error))?;
+        if let Some(folder) = path.parent() {
+            std::fs::create_dir_all(folder).map_err(|error| format!("Failed to create {}: {}", folder.display(), error))?;
+        }
+        std::fs::write(path, json).map_err(|error| format!("Failed to write {}: {}", path.display(), error))?;
+        Ok(())
+    }
+
+    /// Reads a file written by `save_world` and loads it
+    pub fn load_world(&mut self, path: impl AsRef<Path>) -> Result<(), EngineError> {
+        let path = path.as_ref();
+        let json = std::fs::read_to_string(path).map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+        let snapshot: WorldSnapshot =
+            serde_json::from_str(&json).map_err(|error| format!("Failed to parse {}: {}", path.display(), error))?;
+        self.load_snapshot(snapshot)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::component::name::Name;
+    use crate::ecs::component::world_data::WorldData;
+    use serde_json::json;
+
+    #[derive(Serialize, Deserialize)]
+    struct Health(u32);
+    impl Component for Health {}
+
+    fn entity_manager() -> EntityManager {
+        let mut entity_manager = EntityManager::new();
+        entity_manager.register_component::<Health>("Health");
+        entity_manager.register_resource::<WorldData>("WorldData");
+        entity_manager
+    }
+
+    fn entity(id: u32, generation: u32) -> Entity {
+        Entity { id, generation }
+    }
+
+    fn snapshot_of(entities: Vec<(Entity, Value)>) -> WorldSnapshot {
+        let entities = entities
+            .into_iter()
+            .map(|(entity, components)| EntitySnapshot {
+                entity,
+                components: serde_json::from_value(components).expect("Components must be an object"),
+            })
+            .collect();
+        WorldSnapshot { version: SNAPSHOT_VERSION, entities, resources: BTreeMap::new() }
+    }
+
+    // Loads the snapshot into a world with one entity and checks it was turned down without touching the world
+    fn assert_rejected(snapshot: WorldSnapshot, message: &str) {
+        let mut entity_manager = entity_manager();
+        let survivor = entity_manager.create_entity();
+        let error = entity_manager.load_snapshot(snapshot).expect_err("The snapshot should have been rejected");
+        assert!(error.to_string().contains(message), "{}", error);
+        assert!(entity_manager.is_alive(&survivor));
+        assert_eq!(entity_manager.entity_count(), 1);
+    }
+
+    #[test]
+    fn save_and_load_round_trips_the_world() {
+        let mut saved = entity_manager();
+        // Recycled once, so the parent's handle has generation 1
+        let dead = saved.create_entity();
+        saved.despawn(&dead);
+        let parent = saved.create_entity();
+        saved.add_component(&parent, Name::new("parent"));
+        saved.add_component(&parent, Health(10));
+        let child = saved.create_entity();
+        saved.add_component(&child, Health(5));
+        saved.set_parent(&child, &parent);
+        saved.insert_resource(WorldData::new(4, 100, 100));
+        let snapshot = saved.snapshot().expect("Failed to save");
+
+        let mut loaded = entity_manager();
+        let stale = loaded.create_entity();
+        loaded.load_snapshot(snapshot.clone()).expect("Failed to load");
+
+        assert_eq!(parent, entity(0, 1));
+        assert!(!loaded.is_alive(&stale));
+        assert_eq!(loaded.entity_count(), 2);
+        assert_eq!(loaded.get_component::<Health>(&parent).map(|health| health.0), Some(10));
+        assert_eq!(loaded.get_component::<Health>(&child).map(|health| health.0), Some(5));
+        assert_eq!(loaded.find_by_name("parent"), Some(parent));
+        assert_eq!(loaded.ancestors(&child), vec![parent]);
+        assert_eq!(loaded.descendants(&parent), vec![child]);
+        assert_eq!(loaded.resource::<WorldData>().map(|world| world.world_size), Some(4));
+        assert_eq!(loaded.snapshot().expect("Failed to save again"), snapshot);
+    }
+
+    #[test]
+    fn loading_drops_queued_commands() {
+        let mut entity_manager = entity_manager();
+        let snapshot = entity_manager.snapshot().expect("Failed to save");
+        entity_manager.commands().spawn((Health(1),));
+
+        entity_manager.load_snapshot(snapshot).expect("Failed to load");
+        entity_manager.apply_commands();
+        assert_eq!(entity_manager.entity_count(), 0);
+    }
+
+    #[test]
+    fn duplicate_entity_ids_are_rejected() {
+        let snapshot = snapshot_of(vec![(entity(0, 0), json!({})), (entity(0, 1), json!({}))]);
+        assert_rejected(snapshot, "Entity id 0 appears twice");
+    }
+
+    #[test]
+    fn links_to_missing_entities_are_rejected() {
+        let parent = snapshot_of(vec![(entity(0, 0), json!({ "Parent": entity(1, 0) }))]);
+        assert_rejected(parent, "0v0 is linked to 1v0");
+
+        // Same id, but not the generation in the snapshot
+        let children = snapshot_of(vec![
+            (entity(0, 0), json!({ "Children": [entity(1, 0)] })),
+            (entity(1, 2), json!({ "Parent": entity(0, 0) })),
+        ]);
+        assert_rejected(children, "0v0 is linked to 1v0");
+    }
+
+    #[test]
+    fn world_data_with_short_rows_is_rejected() {
+        let mut world = serde_json::to_value(WorldData::new(4, 100, 100)).expect("Failed to save WorldData");
+        world["walls"][2].as_array_mut().expect("walls should be rows").pop();
+        let mut snapshot = snapshot_of(Vec::new());
+        snapshot.resources.insert("WorldData".to_string(), world);
+        assert_rejected(snapshot, "Row 2 of walls has 3 tiles, expected 4");
+    }
+}
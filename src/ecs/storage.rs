@@ -0,0 +1,290 @@
+// src/ecs/storage.rs
+// Description:
+// This module contains the component storage. Every component type gets one sparse set: the components themselves
+// live packed together in a dense Vec, and a sparse Vec indexed by entity id points into it. Iterating a component
+// type walks the dense Vec directly and a lookup by entity id is two array reads instead of a hash and a downcast.
+// Next to every component the set keeps the ticks it was added and last changed at, for change detection.
+
+use std::any::Any;
+use std::ops::{Deref, DerefMut};
+
+// Marks an entity id that has no component in this set
+const EMPTY: u32 = u32::MAX;
+
+/// When a component was added and when it was last written to, in EntityManager change ticks
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ComponentTicks {
+    pub added: u32,
+    pub changed: u32,
+}
+
+impl ComponentTicks {
+    pub fn new(tick: u32) -> Self {
+        ComponentTicks { added: tick, changed: tick }
+    }
+
+    pub fn is_added_after(&self, last_run_tick: u32) -> bool {
+        is_newer(self.added, last_run_tick)
+    }
+
+    pub fn is_changed_after(&self, last_run_tick: u32) -> bool {
+        is_newer(self.changed, last_run_tick)
+    }
+}
+
+/// A mutable borrow of a component that only marks it as changed once something is written through it,
+/// so systems can look at every component they may move without tripping the Changed filter for all of them
+pub struct Mut<'a, T> {
+    value: &'a mut T,
+    ticks: &'a mut ComponentTicks,
+    change_tick: u32,
+}
+
+impl<'a, T> Mut<'a, T> {
+    pub(crate) fn new(value: &'a mut T, ticks: &'a mut ComponentTicks, change_tick: u32) -> Self {
+        Mut { value, ticks, change_tick }
+    }
+
+    /// Gives up the guard for a plain `&mut T`, the component is marked as changed right away
+    pub fn into_inner(self) -> &'a mut T {
+        self.ticks.changed = self.change_tick;
+        self.value
+    }
+}
+
+impl<T> Deref for Mut<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.value
+    }
+}
+
+impl<T> DerefMut for Mut<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.ticks.changed = self.change_tick;
+        self.value
+    }
+}
+
+// Compares ticks so they keep working after the counter wraps around
+fn is_newer(tick: u32, last_run_tick: u32) -> bool {
+    let age = tick.wrapping_sub(last_run_tick);
+    age != 0 && age < u32::MAX / 2
+}
+
+/// The type-erased side of a sparse set, used for anything that doesn't need to know the component type.
+/// Storages are shared with systems running on other threads, so they have to be Send + Sync.
+pub trait ComponentStorage: Any + Send + Sync {
+    fn contains(&self, id: u32) -> bool;
+    fn ticks(&self, id: u32) -> Option<ComponentTicks>;
+    /// Drops the component belonging to the entity, returns whether there was one
+    fn remove_entity(&mut self, id: u32) -> bool;
+    fn len(&self) -> usize;
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Entity ids in the same order as the packed components
+    fn ids(&self) -> &[u32];
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+pub struct SparseSet<T> {
+    sparse: Vec<u32>, // entity id -> index into dense, EMPTY if the entity has no component
+    ids: Vec<u32>,    // dense index -> entity id
+    dense: Vec<T>,
+    ticks: Vec<ComponentTicks>, // same order as dense
+}
+
+impl<T> Default for SparseSet<T> {
+    fn default() -> Self {
+        SparseSet {
+            sparse: Vec::new(),
+            ids: Vec::new(),
+            dense: Vec::new(),
+            ticks: Vec::new(),
+        }
+    }
+}
+
+impl<T> SparseSet<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns where the entity's component sits in the dense Vec
+    pub fn dense_index(&self, id: u32) -> Option<usize> {
+        match self.sparse.get(id as usize) {
+            Some(&index) if index != EMPTY => Some(index as usize),
+            _ => None,
+        }
+    }
+
+    /// Inserts a component at the given change tick, handing back the old one if the entity already had one.
+    /// A replaced component counts as newly added.
+    pub fn insert(&mut self, id: u32, value: T, tick: u32) -> Option<T> {
+        if let Some(index) = self.dense_index(id) {
+            self.ticks[index] = ComponentTicks::new(tick);
+            return Some(std::mem::replace(&mut self.dense[index], value));
+        }
+
+        if id as usize >= self.sparse.len() {
+            self.sparse.resize(id as usize + 1, EMPTY);
+        }
+        self.sparse[id as usize] = self.dense.len() as u32;
+        self.ids.push(id);
+        self.dense.push(value);
+        self.ticks.push(ComponentTicks::new(tick));
+        None
+    }
+
+    /// Removes a component by swapping the last one into its slot so the dense Vec stays packed
+    pub fn remove(&mut self, id: u32) -> Option<T> {
+        let index = self.dense_index(id)?;
+        self.sparse[id as usize] = EMPTY;
+
+        let value = self.dense.swap_remove(index);
+        self.ids.swap_remove(index);
+        self.ticks.swap_remove(index);
+        if let Some(&moved_id) = self.ids.get(index) {
+            self.sparse[moved_id as usize] = index as u32;
+        }
+        Some(value)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&T> {
+        self.dense_index(id).map(|index| &self.dense[index])
+    }
+
+    /// Borrows a component mutably, writing through it marks it as changed at the given tick
+    pub fn get_mut(&mut self, id: u32, tick: u32) -> Option<Mut<'_, T>> {
+        let index = self.dense_index(id)?;
+        Some(Mut::new(&mut self.dense[index], &mut self.ticks[index], tick))
+    }
+
+    /// Raw pointer to the packed components, lets queries hand out several `&mut T` at once
+    /// without reborrowing the whole Vec for each one
+    pub fn dense_ptr(&mut self) -> *mut T {
+        self.dense.as_mut_ptr()
+    }
+
+    /// Raw pointer to the packed ticks, same reasoning as `dense_ptr`
+    pub fn ticks_ptr(&mut self) -> *mut ComponentTicks {
+        self.ticks.as_mut_ptr()
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.dense
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (u32, &T)> {
+        self.ids.iter().copied().zip(self.dense.iter())
+    }
+}
+
+impl<T: Send + Sync + 'static> ComponentStorage for SparseSet<T> {
+    fn contains(&self, id: u32) -> bool {
+        self.dense_index(id).is_some()
+    }
+
+    fn ticks(&self, id: u32) -> Option<ComponentTicks> {
+        self.dense_index(id).map(|index| self.ticks[index])
+    }
+
+    fn remove_entity(&mut self, id: u32) -> bool {
+        self.remove(id).is_some()
+    }
+
+    fn len(&self) -> usize {
+        self.dense.len()
+    }
+
+    fn ids(&self) -> &[u32] {
+        &self.ids
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_from_the_middle_moves_the_last_component_into_its_slot() {
+        let mut set = SparseSet::new();
+        set.insert(1, "a", 10);
+        set.insert(5, "b", 20);
+        set.insert(3, "c", 30);
+
+        assert_eq!(set.remove(5), Some("b"));
+
+        // 3 was last and now sits where 5 was, with its own ticks
+        assert_eq!(set.ids(), &[1, 3]);
+        assert_eq!(set.dense_index(3), Some(1));
+        assert_eq!(set.get(3), Some(&"c"));
+        assert_eq!(set.ticks(3).map(|ticks| ticks.added), Some(30));
+        assert_eq!(set.get(1), Some(&"a"));
+        assert_eq!(set.ticks(1).map(|ticks| ticks.added), Some(10));
+        assert!(!set.contains(5));
+        assert_eq!(set.get(5), None);
+    }
+
+    #[test]
+    fn removing_the_last_component() {
+        let mut set = SparseSet::new();
+        set.insert(0, 1, 0);
+        set.insert(2, 2, 0);
+
+        assert_eq!(set.remove(2), Some(2));
+        assert_eq!(set.ids(), &[0]);
+        assert_eq!(set.get(0), Some(&1));
+        assert_eq!(set.remove(2), None);
+    }
+
+    #[test]
+    fn removed_id_can_be_inserted_again() {
+        let mut set = SparseSet::new();
+        set.insert(4, "old", 1);
+        set.insert(7, "other", 2);
+        set.remove(4);
+
+        assert_eq!(set.insert(4, "new", 3), None);
+        assert_eq!(set.get(4), Some(&"new"));
+        assert_eq!(set.ticks(4).map(|ticks| (ticks.added, ticks.changed)), Some((3, 3)));
+        assert_eq!(set.get(7), Some(&"other"));
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn inserting_over_a_component_replaces_it_and_its_ticks() {
+        let mut set = SparseSet::new();
+        set.insert(2, "first", 1);
+        assert_eq!(set.insert(2, "second", 5), Some("first"));
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.ticks(2).map(|ticks| ticks.added), Some(5));
+    }
+
+    #[test]
+    fn only_writing_through_get_mut_marks_a_component_as_changed() {
+        let mut set = SparseSet::new();
+        set.insert(0, 1, 1);
+
+        let value = set.get_mut(0, 2).map(|value| *value);
+        assert_eq!(value, Some(1));
+        assert_eq!(set.ticks(0).map(|ticks| ticks.changed), Some(1));
+
+        if let Some(mut value) = set.get_mut(0, 3) {
+            *value += 1;
+        }
+        assert_eq!(set.get(0), Some(&2));
+        assert_eq!(set.ticks(0).map(|ticks| ticks.changed), Some(3));
+    }
+}
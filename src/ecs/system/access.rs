@@ -0,0 +1,104 @@
+// src/ecs/system/access.rs
+// Description:
+// This module contains SystemAccess, the list of components and resources a system reads and writes.
+// The system manager uses it to put systems that don't conflict into the same batch and run them at the same time,
+// SystemContext uses it to check that a system only touches what it declared.
+// Systems using non-send resources like the window declare `main_thread`, they always run on the main thread.
+
+use crate::ecs::component::Component;
+use crate::ecs::query::{Access, Query, QueryFilter};
+use std::any::{type_name, TypeId};
+
+#[derive(Clone, Debug, Default)]
+pub struct SystemAccess {
+    component_reads: Vec<(TypeId, &'static str)>,
+    component_writes: Vec<(TypeId, &'static str)>,
+    resource_reads: Vec<(TypeId, &'static str)>,
+    resource_writes: Vec<(TypeId, &'static str)>,
+    main_thread: bool,
+}
+
+impl SystemAccess {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn read<T: Component>(mut self) -> Self {
+        self.component_reads.push((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    pub fn write<T: Component>(mut self) -> Self {
+        self.component_writes.push((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    /// Works for both kinds of resources, non-send ones also need `main_thread`
+    pub fn read_resource<T: 'static>(mut self) -> Self {
+        self.resource_reads.push((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    pub fn write_resource<T: 'static>(mut self) -> Self {
+        self.resource_writes.push((TypeId::of::<T>(), type_name::<T>()));
+        self
+    }
+
+    /// Declares everything a query and its filter borrow, e.g. `query::<(Entity, &mut Transform), With<PlayerData>>()`
+    pub fn query<Q: Query, F: QueryFilter>(mut self) -> Self {
+        let mut access = Access::default();
+        Q::access(&mut access);
+        F::access(&mut access);
+        self.component_reads.extend(access.reads().copied());
+        self.component_writes.extend(access.writes().copied());
+        self
+    }
+
+    /// The system uses non-send resources and has to run on the main thread
+    pub fn main_thread(mut self) -> Self {
+        self.main_thread = true;
+        self
+    }
+
+    pub fn runs_on_main_thread(&self) -> bool {
+        self.main_thread
+    }
+
+    pub fn can_read_component(&self, type_id: TypeId) -> bool {
+        contains(&self.component_reads, type_id) || self.can_write_component(type_id)
+    }
+
+    pub fn can_write_component(&self, type_id: TypeId) -> bool {
+        contains(&self.component_writes, type_id)
+    }
+
+    pub fn can_read_resource(&self, type_id: TypeId) -> bool {
+        contains(&self.resource_reads, type_id) || self.can_write_resource(type_id)
+    }
+
+    pub fn can_write_resource(&self, type_id: TypeId) -> bool {
+        contains(&self.resource_writes, type_id)
+    }
+
+    /// Returns the name of a type one system writes while the other one reads or writes it,
+    /// None if the two systems can run at the same time
+    pub fn conflict_with(&self, other: &SystemAccess) -> Option<&'static str> {
+        conflict(&self.component_writes, &other.component_reads)
+            .or_else(|| conflict(&self.component_writes, &other.component_writes))
+            .or_else(|| conflict(&other.component_writes, &self.component_reads))
+            .or_else(|| conflict(&self.resource_writes, &other.resource_reads))
+            .or_else(|| conflict(&self.resource_writes, &other.resource_writes))
+            .or_else(|| conflict(&other.resource_writes, &self.resource_reads))
+    }
+}
+
+fn contains(types: &[(TypeId, &'static str)], type_id: TypeId) -> bool {
+    types.iter().any(|(id, _)| *id == type_id)
+}
+
+fn conflict(writes: &[(TypeId, &'static str)], others: &[(TypeId, &'static str)]) -> Option<&'static str> {
+    writes
+        .iter()
+        .find(|(id, _)| contains(others, *id))
+        .map(|(_, name)| *name)
+}
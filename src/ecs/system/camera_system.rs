@@ -1,26 +1,54 @@
-use sdl2::pixels::Color;
-use sdl2::sys::{ldiv_t, XOC};
-
 use crate::ecs::component::camera_data::CameraData;
+use crate::ecs::component::name::Name;
 use crate::ecs::component::player_data::PlayerData;
-use crate::ecs::component::transform_data::Transform;
-use crate::ecs::component::world_data::WorldData;
-use crate::ecs::entity_manager::EntityManager;
-use crate::sdl_window_manager::SDLWindowManager;
-use std::any::Any;
-use std::cell::RefCell;
-use std::f32::consts::PI;
-use std::f32::INFINITY;
-use std::rc::Rc;
-
-use super::System;
+use crate::ecs::component::sprite::Sprite;
+use crate::ecs::entity_manager::Entity;
+use crate::ecs::component::transform_data::GlobalTransform;
+use crate::ecs::component::world_data::{Surface, WorldData};
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::ecs::query::With;
+use crate::error::EngineError;
+use crate::log_warn;
+use crate::render::texture::{Texture, TextureAtlas};
+use crate::render::Renderer;
+use std::any::{Any, TypeId};
+
+use super::world_system::WorldSystem;
+use super::{Stage, System};
 
+#[derive(Default)]
 pub struct Camera_System {
-    pub window_manager: Rc<RefCell<SDLWindowManager>>,
+    // Distance to the wall in every column of the last frame, sprites behind it are hidden
+    z_buffer: Vec<f32>,
+    // RGBA image the floor and ceiling are cast into, kept so a frame doesn't allocate a new one
+    floor_image: Vec<u8>,
+    // Scratch image every sprite is drawn into in turn before it goes to the renderer
+    sprite_image: Vec<u8>,
+    // Set once walls had to be drawn without their texture, so the warning isn't repeated every frame
+    warned_missing_texture: bool,
 }
 
 impl Camera_System {
-    fn cast_rays(&mut self, player: &Transform, world_data: &WorldData, camera: &CameraData) {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The wall distance in every column, filled by `cast_rays`
+    pub fn z_buffer(&self) -> &[f32] {
+        &self.z_buffer
+    }
+
+    /// Draws the first person view from `player` into the renderer, the system calls this every frame.
+    /// Public so the view can be rendered without an entity manager, e.g. for the golden image tests.
+    pub fn cast_rays(
+        &mut self,
+        renderer: &dyn Renderer,
+        player: &GlobalTransform,
+        world_data: &WorldData,
+        camera: &CameraData,
+        textures: &TextureAtlas,
+    ) {
         let map = &world_data.get_wall_array(); // Assuming world_data contains a 2D map array
         let _map_width = map[0].len() as i32;
         let _map_height = map.len() as i32;
@@ -29,27 +57,20 @@ impl Camera_System {
         let pos_y = player.position.y();
         let dir_x = player.position.dir_x();
         let dir_y = player.position.dir_y();
-        let fov = camera.fov.clone(); // Assuming player has a method to get FOV
-    
-        let window_height;
-        let window_width;
-        {
-            let window_manager = self.window_manager.borrow();
-            window_height = window_manager.get_window_size().0 as i32;
-            window_width = window_manager.get_window_size().1 as i32;
-        }
     
-        // Introduce a resolution variable to increase the number of rays fired
-        let resolution = 2; // Increase this value to increase the resolution
-        let rays_to_cast = window_width * resolution;
+        let (window_width, window_height) = renderer.get_window_size();
+        let (window_width, window_height) = (window_width as i32, window_height as i32);
         let camera_plane = camera.calculate_camera_plane(dir_x, dir_y);
+
+        // Floor and ceiling first, the walls are drawn over them
+        self.cast_floor_and_ceiling(renderer, (pos_x, pos_y), (dir_x, dir_y), camera_plane, world_data, textures);
     
-        // Calculate the center column index
-        let center_column = rays_to_cast / 2;
+        self.z_buffer.clear();
+        self.z_buffer.resize(window_width.max(0) as usize, f32::INFINITY);
     
-        for ray_index in 0..rays_to_cast {
-            // Adjust camera_x calculation to ensure the center column corresponds to the player's forward direction
-            let camera_x = (ray_index as f32 - center_column as f32) / center_column as f32; // x-coordinate in camera space
+        // One ray for every column of the window, the center column looks straight ahead
+        for column in 0..window_width {
+            let camera_x = 2.0 * column as f32 / window_width as f32 - 1.0; // x-coordinate in camera space, -1 to 1
             let ray_dir_x = dir_x + camera_plane.0 * camera_x;
             let ray_dir_y = dir_y + camera_plane.1 * camera_x;
     
@@ -64,7 +85,6 @@ impl Camera_System {
             // Length of ray from one x or y-side to next x or y-side
             let delta_dist_x = (1.0 / ray_dir_x).abs();
             let delta_dist_y = (1.0 / ray_dir_y).abs();
-            let mut perp_wall_dist = 0.0;
     
             // Direction to step in x and y (+1 or -1)
             let step_x;
@@ -110,50 +130,55 @@ impl Camera_System {
             }
     
             // Calculate distance projected on camera direction (oblique distance will give fisheye effect!)
-            if side == 0 {
-                perp_wall_dist =
-                    ((map_x as f32 - pos_x + (1.0 - step_x as f32) / 2.0) / ray_dir_x).abs();
+            let perp_wall_dist = if side == 0 {
+                ((map_x as f32 - pos_x + (1.0 - step_x as f32) / 2.0) / ray_dir_x).abs()
             } else {
-                perp_wall_dist =
-                    ((map_y as f32 - pos_y + (1.0 - step_y as f32) / 2.0) / ray_dir_y).abs();
-            }
+                ((map_y as f32 - pos_y + (1.0 - step_y as f32) / 2.0) / ray_dir_y).abs()
+            };
+            self.z_buffer[column as usize] = perp_wall_dist;
     
             // Calculate height of line to draw on screen
-            let scale_factor = 1.5; // Adjust this factor to make walls higher or lower
-            let line_height = (window_height as f32 / perp_wall_dist * scale_factor) as i32;
+            let line_height = (window_height as f32 / perp_wall_dist * WALL_HEIGHT_SCALE) as i32;
     
             // Calculate the lowest and highest pixel to fill in the current stripe
             let draw_start = (-line_height / 2 + window_height / 2).max(0);
-            let draw_end = (line_height / 2 + window_height / 2).min(window_height as i32 - 1);
+            let draw_end = (line_height / 2 + window_height / 2).min(window_height - 1);
     
-            // Calculate the width of each slice based on the number of rays and the window width
-            let slice_width = (window_width as f32 / rays_to_cast as f32).ceil() as i32;
+            // Where along the wall the ray hit, 0 to 1 across the tile. Flipped for the faces seen from the other side
+            // so textures aren't mirrored
+            let wall_hit = if side == 0 {
+                pos_y + perp_wall_dist * ray_dir_y
+            } else {
+                pos_x + perp_wall_dist * ray_dir_x
+            };
+            let mut texture_x = (wall_hit / world_data.get_cell_size() as f32).fract();
+            if (side == 0 && ray_dir_x > 0.0) || (side == 1 && ray_dir_y < 0.0) {
+                texture_x = 1.0 - texture_x;
+            }
+            let tile = world_data.get_map_coord_from_world_pos(map_x as f32, map_y as f32);
     
             // Shading
-            let max_distance = 50.0; // Increase the maximum distance for a more gradual shading effect
-            let distance_factor = (1.0 - (perp_wall_dist / max_distance)).max(0.0).min(1.0);
-            let angle_factor = (ray_dir_x * dir_x + ray_dir_y * dir_y).max(0.0).min(1.0);
-            let shading_factor = distance_factor * angle_factor;
-    
-            // Define the colors for shading
-            let white = (255, 255, 255);
-            let darkest = (150, 150, 150); // Dark gray
-    
-            // Interpolate between white and dark gray based on the shading factor
-            let shaded_color = (
-                (white.0 as f32 * shading_factor + darkest.0 as f32 * (1.0 - shading_factor)) as u8,
-                (white.1 as f32 * shading_factor + darkest.1 as f32 * (1.0 - shading_factor)) as u8,
-                (white.2 as f32 * shading_factor + darkest.2 as f32 * (1.0 - shading_factor)) as u8,
-            );
-            // Draw the wall slice for the current ray
-            for x in (ray_index * slice_width)..((ray_index + 1) * slice_width) {
-                if x >= 0 && x < window_width {
-                    self.draw_wall_slice_with_shading(
-                        x,
-                        draw_start,
-                        draw_end - draw_start,
-                        shaded_color,
-                    );
+            let angle_factor = (ray_dir_x * dir_x + ray_dir_y * dir_y).clamp(0.0, 1.0);
+            let mut brightness = distance_brightness(perp_wall_dist, angle_factor);
+            // North and south faces are darker, so corners stand out like in Wolfenstein
+            if side == 1 {
+                brightness *= SIDE_SHADE;
+            }
+    
+            let texture_index = world_data.wall_texture(tile.0, tile.1);
+            match textures.get(texture_index) {
+                Some(texture) => {
+                    let wall_top = window_height / 2 - line_height / 2;
+                    self.draw_textured_column(renderer, column, (draw_start, draw_end), wall_top, line_height, texture, texture_x, brightness);
+                }
+                // Without a texture the wall is drawn in flat shaded white so it doesn't disappear
+                None => {
+                    if !self.warned_missing_texture {
+                        log_warn!("No wall texture {} in the texture atlas, drawing walls untextured", texture_index);
+                        self.warned_missing_texture = true;
+                    }
+                    let (r, g, b) = shade([255, 255, 255, 255], brightness);
+                    renderer.draw_filled_rect(column, draw_start, 1, (draw_end - draw_start + 1) as u32, r, g, b);
                 }
             }
         }
@@ -167,47 +192,262 @@ impl Camera_System {
     //     let window_manager = self.window_manager.borrow();
     //     window_manager.draw_line(x1, y1, x2, y2, 255, 255, 255)
     // }
-    fn draw_wall_slice_with_shading(&mut self, x: i32, y: i32, height: i32, color: (u8, u8, u8)) {
-        let window_manager = self.window_manager.borrow();
-        window_manager.draw_rect(x, y, 1, height as u32, color.0, color.1, color.2);
+    // Casts one row of floor at a time: every pixel of a row below the horizon is the same distance away, so the
+    // floor position only has to be stepped along the row. The ceiling is the floor mirrored at the horizon.
+    // Everything is drawn into one image which goes to the renderer in a single call, the image is reused
+    // from frame to frame.
+    fn cast_floor_and_ceiling(
+        &mut self,
+        renderer: &dyn Renderer,
+        (pos_x, pos_y): (f32, f32),
+        (dir_x, dir_y): (f32, f32),
+        camera_plane: (f32, f32),
+        world_data: &WorldData,
+        textures: &TextureAtlas,
+    ) {
+        let (width, height) = renderer.get_window_size();
+        // Cleared to transparent, surfaces without a texture leave the background showing
+        let image = &mut self.floor_image;
+        image.clear();
+        image.resize((width * height * 4) as usize, 0);
+        let cell_size = world_data.get_cell_size() as f32;
+
+        // The rays through the left and right edge of the window
+        let (left_x, left_y) = (dir_x - camera_plane.0, dir_y - camera_plane.1);
+        let (right_x, right_y) = (dir_x + camera_plane.0, dir_y + camera_plane.1);
+
+        let horizon = height as i32 / 2;
+        for y in (horizon + 1)..height as i32 {
+            // The inverse of the wall height, a wall at this distance would end on this row
+            let row_distance = height as f32 * WALL_HEIGHT_SCALE / (2.0 * (y - horizon) as f32);
+            let step_x = row_distance * (right_x - left_x) / width as f32;
+            let step_y = row_distance * (right_y - left_y) / width as f32;
+            let mut floor_x = pos_x + row_distance * left_x;
+            let mut floor_y = pos_y + row_distance * left_y;
+            let brightness = distance_brightness(row_distance, 1.0);
+            let ceiling_y = height as i32 - 1 - y;
+
+            for x in 0..width {
+                let (tile_x, tile_y) = world_data.get_map_coord_from_world_pos(floor_x, floor_y);
+                let (u, v) = ((floor_x / cell_size).rem_euclid(1.0), (floor_y / cell_size).rem_euclid(1.0));
+                for (row, surface) in [(y, world_data.floor(tile_x, tile_y)), (ceiling_y, world_data.ceiling(tile_x, tile_y))] {
+                    if let Some((r, g, b)) = surface_color(surface, textures, u, v, brightness) {
+                        let index = ((row as u32 * width + x) * 4) as usize;
+                        image[index..index + 4].copy_from_slice(&[r, g, b, 255]);
+                    }
+                }
+                floor_x += step_x;
+                floor_y += step_y;
+            }
+        }
+        renderer.draw_image(0, 0, width, height, image);
+    }
+
+    /// Draws the sprites as billboards over the view `cast_rays` drew last, furthest first and hidden where
+    /// a wall in the z-buffer is closer
+    pub fn draw_sprites(
+        &mut self,
+        renderer: &dyn Renderer,
+        player: &GlobalTransform,
+        camera: &CameraData,
+        sprites: &[(&GlobalTransform, &Sprite)],
+        textures: &TextureAtlas,
+    ) {
+        let (width, height) = renderer.get_window_size();
+        let (dir_x, dir_y) = (player.position.dir_x(), player.position.dir_y());
+        let (plane_x, plane_y) = camera.calculate_camera_plane(dir_x, dir_y);
+        let determinant = plane_x * dir_y - dir_x * plane_y;
+        if determinant == 0.0 {
+            return;
+        }
+
+        // Into camera space: depth along the view direction and the offset along the camera plane, both in the
+        // units perp_wall_dist uses
+        let mut projected: Vec<(f32, f32, &Sprite)> = sprites
+            .iter()
+            .filter_map(|(transform, sprite)| {
+                let relative_x = transform.position.x() - player.position.x();
+                let relative_y = transform.position.y() - player.position.y();
+                let offset = (dir_y * relative_x - dir_x * relative_y) / determinant;
+                let depth = (-plane_y * relative_x + plane_x * relative_y) / determinant;
+                (depth > NEAR_PLANE).then_some((depth, offset, *sprite))
+            })
+            .collect();
+        projected.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+        for (depth, offset, sprite) in projected {
+            let Some(texture) = textures.get(sprite.texture as usize) else {
+                continue;
+            };
+            // Sized like a wall at the same depth and standing on the floor
+            let wall_height = height as f32 * WALL_HEIGHT_SCALE / depth;
+            let sprite_height = wall_height * sprite.scale;
+            let sprite_width = sprite_height * texture.width() as f32 / texture.height() as f32;
+            let center_x = width as f32 / 2.0 * (1.0 + offset / depth);
+            let bottom = height as f32 / 2.0 + wall_height / 2.0;
+            let (left, top) = (center_x - sprite_width / 2.0, bottom - sprite_height);
+
+            // The part of the sprite inside the window
+            let start_x = left.max(0.0) as i32;
+            let end_x = (left + sprite_width).min(width as f32).ceil() as i32;
+            let start_y = top.max(0.0) as i32;
+            let end_y = (top + sprite_height).min(height as f32).ceil() as i32;
+            if start_x >= end_x || start_y >= end_y {
+                continue;
+            }
+
+            let brightness = distance_brightness(depth, 1.0);
+            let (image_width, image_height) = ((end_x - start_x) as u32, (end_y - start_y) as u32);
+            // Cleared to transparent so columns behind a wall let the view show through
+            let image = &mut self.sprite_image;
+            image.clear();
+            image.resize((image_width * image_height * 4) as usize, 0);
+            for x in start_x..end_x {
+                if self.z_buffer.get(x as usize).is_some_and(|&wall| wall <= depth) {
+                    continue;
+                }
+                let u = (x as f32 + 0.5 - left) / sprite_width;
+                for y in start_y..end_y {
+                    let v = (y as f32 + 0.5 - top) / sprite_height;
+                    let color = texture.sample(u, v);
+                    if color[3] == 0 {
+                        continue;
+                    }
+                    let (r, g, b) = shade(color, brightness);
+                    let index = (((y - start_y) as u32 * image_width + (x - start_x) as u32) * 4) as usize;
+                    image[index..index + 4].copy_from_slice(&[r, g, b, color[3]]);
+                }
+            }
+            renderer.draw_image(start_x, start_y, image_width, image_height, image);
+        }
+    }
+
+    // Draws the visible rows of a wall column, `wall_top` and `line_height` are the whole wall even where it is
+    // off screen so the texture lines up. Pixels sampling the same texel are drawn as one rect.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_textured_column(
+        &mut self,
+        renderer: &dyn Renderer,
+        x: i32,
+        (draw_start, draw_end): (i32, i32),
+        wall_top: i32,
+        line_height: i32,
+        texture: &Texture,
+        texture_x: f32,
+        brightness: f32,
+    ) {
+        let mut run_start = draw_start;
+        let mut run_color = None;
+        for y in draw_start..=draw_end {
+            let texture_y = (y - wall_top) as f32 / line_height.max(1) as f32;
+            let color = shade(texture.sample(texture_x, texture_y), brightness);
+            if run_color != Some(color) {
+                if let Some((r, g, b)) = run_color {
+                    renderer.draw_filled_rect(x, run_start, 1, (y - run_start) as u32, r, g, b);
+                }
+                run_start = y;
+                run_color = Some(color);
+            }
+        }
+        // The last run goes down to draw_end, which is the last row of the wall
+        if let Some((r, g, b)) = run_color {
+            renderer.draw_filled_rect(x, run_start, 1, (draw_end + 1 - run_start) as u32, r, g, b);
+        }
+    }
+}
+
+// Walls are this many times the window height tall at a distance of one ray direction
+const WALL_HEIGHT_SCALE: f32 = 1.5;
+// Sprites closer than this, or behind the camera, aren't drawn
+const NEAR_PLANE: f32 = 0.05;
+// Walls hit at this distance or further are drawn with DARKEST_SHADE
+const MAX_SHADING_DISTANCE: f32 = 50.0;
+// Brightness of the furthest walls, 150 out of 255
+const DARKEST_SHADE: f32 = 150.0 / 255.0;
+// North and south faces are multiplied with this
+const SIDE_SHADE: f32 = 0.75;
+
+// Brightness between DARKEST_SHADE and 1 for something `distance` away, as a ray-direction multiple
+fn distance_brightness(distance: f32, angle_factor: f32) -> f32 {
+    let distance_factor = (1.0 - (distance / MAX_SHADING_DISTANCE)).clamp(0.0, 1.0);
+    let shading_factor = distance_factor * angle_factor;
+    shading_factor + DARKEST_SHADE * (1.0 - shading_factor)
+}
+
+// The shaded colour of a floor or ceiling at the texture coordinates, None if its texture isn't in the atlas
+fn surface_color(surface: Surface, textures: &TextureAtlas, u: f32, v: f32, brightness: f32) -> Option<(u8, u8, u8)> {
+    match surface {
+        Surface::Texture(index) => textures.get(index as usize).map(|texture| shade(texture.sample(u, v), brightness)),
+        Surface::Color(r, g, b) => Some(shade([r, g, b, 255], brightness)),
     }
 }
+
+fn shade(color: [u8; 4], brightness: f32) -> (u8, u8, u8) {
+    let scale = |channel: u8| (channel as f32 * brightness).clamp(0.0, 255.0) as u8;
+    (scale(color[0]), scale(color[1]), scale(color[2]))
+}
+
 impl System for Camera_System {
-    fn update(&mut self, entity_manager: &mut EntityManager, _entity_id: u32) {
-        let player_entities = entity_manager.query_entities::<PlayerData>();
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        let mut players = context
+            .query_ref_filtered::<(Entity, &GlobalTransform, Option<&CameraData>), With<PlayerData>>();
 
         // Ensure there's exactly one player entity
-        if player_entities.len() != 1 {
-            panic!("There must be exactly one player entity with PlayerData.");
+        if players.len() != 1 {
+            return Err(EngineError::UnexpectedEntityCount {
+                what: "player entity with PlayerData",
+                expected: 1,
+                found: players.len(),
+            });
         }
 
-        let player_entity_id = player_entities[0];
+        // Get the player's transform and camera
+        let (entity, transform, camera) = players.next().expect("The player count was checked");
+        let camera = camera.ok_or_else(|| EngineError::missing_component::<CameraData>(context.entity_label(&entity)))?;
 
-        // Get the player's transform
-        let transform = entity_manager
-            .get_component::<Transform>(&player_entity_id)
-            .expect("Failed to get Transform component");
+        // Get the world data and the window to draw into
+        let world_data = context
+            .resource::<WorldData>()
+            .ok_or_else(EngineError::missing_resource::<WorldData>)?;
+        let renderer = context
+            .renderer()
+            .ok_or_else(EngineError::missing_resource::<Box<dyn Renderer>>)?;
 
-        // Get the world data
-        let world_entities = entity_manager.query_entities::<WorldData>();
-        if world_entities.len() != 1 {
-            panic!("There must be exactly one world entity with WorldData.");
-        }
+        let textures = context
+            .resource::<TextureAtlas>()
+            .ok_or_else(EngineError::missing_resource::<TextureAtlas>)?;
+
+        // Cast rays from the player's position
+        self.cast_rays(renderer, transform, world_data, camera, textures);
 
-        let world_data = entity_manager
-            .get_component::<WorldData>(&world_entities[0])
-            .expect("Failed to get WorldData component");
+        // The player's own sprite would cover the whole view
+        let sprites: Vec<(&GlobalTransform, &Sprite)> = context
+            .query_ref::<(Entity, &GlobalTransform, &Sprite)>()
+            .filter(|(sprite_entity, _, _)| *sprite_entity != entity)
+            .map(|(_, transform, sprite)| (transform, sprite))
+            .collect();
+        self.draw_sprites(renderer, transform, camera, &sprites, textures);
+        Ok(())
+    }
 
-        let camera = entity_manager
-            .get_component::<CameraData>(&player_entity_id)
-            .expect("Failed to get CameraData component");
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .query::<(Entity, &GlobalTransform, Option<&CameraData>), With<PlayerData>>()
+            .query::<(Entity, &GlobalTransform, &Sprite), ()>()
+            .read::<Name>() // for diagnostics
+            .read_resource::<WorldData>()
+            .read_resource::<TextureAtlas>()
+            .write_resource::<Box<dyn Renderer>>()
+            .main_thread()
+    }
 
-        // Cast rays from the player's position
-        self.cast_rays(transform, world_data, camera);
+    fn stage(&self) -> Stage {
+        Stage::Render
     }
 
-    fn priority(&self) -> u32 {
-        150
+    // The first person view is drawn over the top-down debug view
+    fn after(&self) -> Vec<TypeId> {
+        vec![TypeId::of::<WorldSystem>()]
     }
 
     fn as_any(&self) -> &dyn Any {
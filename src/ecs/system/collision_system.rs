@@ -1,55 +1,92 @@
 // src/ecs/systems/collision_system.rs
 // Description:
 // This module contains the collision system which is responsible for checking if entities are colliding with each other or the boundaries of the screen.
+// Every collision is also sent as a CollisionEvent so other systems can react to it.
 
 use crate::ecs::component::{
     collision_data::CollisionData, transform_data::Transform, render_data::RenderData,
 };
-use crate::ecs::entity_manager::{Entity, EntityManager};
-use crate::ecs::system::System;
+use crate::ecs::entity_manager::Entity;
+use crate::ecs::event::Events;
+use crate::ecs::query::With;
+use crate::ecs::storage::Mut;
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::ecs::system::{Stage, System};
+use crate::error::EngineError;
 use std::any::Any;
 
 pub struct CollisionSystem;
 
+/// Sent when an entity collides with something
+#[derive(Clone, Debug)]
+pub struct CollisionEvent {
+    pub entity: Entity,
+    pub other: Option<Entity>, // None when the entity hit the screen boundary
+}
+
 // Define screen boundaries
 const SCREEN_WIDTH: f32 = 800.0;
 const SCREEN_HEIGHT: f32 = 600.0;
 
+impl CollisionSystem {
+    /// Bounces the entity off the screen boundaries, returns whether it hit one.
+    /// The Transform is only written to when it did, so it isn't marked as changed otherwise.
+    fn bounce_off_boundaries(transform: &mut Mut<Transform>, size: f32) -> bool {
+        let pos_x = transform.position.x();
+        let pos_y = transform.position.y();
+        let mut collided = false;
+
+        // Check collision with screen boundaries and adjust transform.velocity accordingly
+        if pos_x < 0.0 {
+            transform.velocity.delta_x = transform.velocity.delta_x.abs(); // Move right
+            collided = true;
+        }
+        if pos_x + size > SCREEN_WIDTH {
+            transform.velocity.delta_x = -transform.velocity.delta_x.abs(); // Move left
+            collided = true;
+        }
+        if pos_y < 0.0 {
+            transform.velocity.delta_y = transform.velocity.delta_y.abs(); // Move down
+            collided = true;
+        }
+        if pos_y + size > SCREEN_HEIGHT {
+            transform.velocity.delta_y = -transform.velocity.delta_y.abs(); // Move up
+            collided = true;
+        }
+        collided
+    }
+}
+
 impl System for CollisionSystem {
-    fn update(&mut self, entity_manager: &mut EntityManager, _entity_id: u32) {
-        // Collect the necessary data immutably first
-        let mut collision_entities: Vec<(u32, f32, f32, f32)> = Vec::new();
-
-        for entity in entity_manager.query_entities::<CollisionData>() {
-            if let Some(transform) = entity_manager.get_component::<Transform>(entity) {
-                if let Some(render_data) = entity_manager.get_component::<RenderData>(entity) {
-                    collision_entities.push((entity.id, transform.position.x(), transform.position.y(), render_data.size));
-                }
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        let mut collisions = Vec::new();
+
+        let colliders = context
+            .query_filtered::<(Entity, &mut Transform, &RenderData), With<CollisionData>>();
+        for (entity, mut transform, render_data) in colliders {
+            if CollisionSystem::bounce_off_boundaries(&mut transform, render_data.size) {
+                collisions.push(CollisionEvent { entity, other: None });
             }
         }
 
-        // Perform the mutable updates in a separate loop
-        for (entity_id, pos_x, pos_y, size) in collision_entities {
-            if let Some(transform) = entity_manager.get_component_mut::<Transform>(&Entity { id: entity_id }) {
-                // Check collision with screen boundaries and adjust transform.velocity accordingly
-                if pos_x < 0.0 {
-                    transform.velocity.delta_x = transform.velocity.delta_x.abs(); // Move right
-                }
-                if pos_x + size > SCREEN_WIDTH {
-                    transform.velocity.delta_x = -transform.velocity.delta_x.abs(); // Move left
-                }
-                if pos_y < 0.0 {
-                    transform.velocity.delta_y = transform.velocity.delta_y.abs(); // Move down
-                }
-                if pos_y + size > SCREEN_HEIGHT {
-                    transform.velocity.delta_y = -transform.velocity.delta_y.abs(); // Move up
-                }
+        // The writer borrows the entity manager so the events are sent once the query is done
+        if let Some(mut writer) = context.event_writer::<CollisionEvent>() {
+            for collision in collisions {
+                writer.send(collision);
             }
         }
+        Ok(())
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .query::<(Entity, &mut Transform, &RenderData), With<CollisionData>>()
+            .write_resource::<Events<CollisionEvent>>()
     }
 
-    fn priority(&self) -> u32 {
-        2
+    fn stage(&self) -> Stage {
+        Stage::Physics
     }
 
     fn as_any(&self) -> &dyn Any {
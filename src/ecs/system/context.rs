@@ -0,0 +1,202 @@
+// src/ecs/system/context.rs
+// Description:
+// This module contains SystemContext, what a system gets to work with while it runs. It wraps a shared borrow of
+// the entity manager so several systems can hold one at the same time on different threads. Every query and
+// borrow is checked against the access the system declared, so a system can't race another one in its batch.
+// Structural changes go through `commands()`, they are applied once the batch is done.
+
+use crate::ecs::commands::{CommandQueue, Commands};
+use crate::ecs::component::name::Name;
+use crate::ecs::component::Component;
+use crate::ecs::entity_manager::{Entity, EntityManager};
+use crate::ecs::event::{EventWriter, Events};
+use crate::ecs::names::entity_label;
+use crate::ecs::query::{Access, Query, QueryFilter, QueryIter, ReadOnlyQuery};
+use crate::ecs::storage::Mut;
+use crate::ecs::system::access::SystemAccess;
+use crate::render::Renderer;
+use std::any::{type_name, TypeId};
+
+pub struct SystemContext<'w> {
+    entity_manager: &'w EntityManager,
+    access: &'w SystemAccess,
+    system_name: &'static str,
+    commands: CommandQueue,
+    change_tick: u32,   // tick this run marks changes with
+    last_run_tick: u32, // tick of the system's previous run, for the Added and Changed filters
+}
+
+impl<'w> SystemContext<'w> {
+    pub(crate) fn new(
+        entity_manager: &'w EntityManager,
+        access: &'w SystemAccess,
+        system_name: &'static str,
+        change_tick: u32,
+        last_run_tick: u32,
+    ) -> Self {
+        SystemContext {
+            entity_manager,
+            access,
+            system_name,
+            commands: CommandQueue::default(),
+            change_tick,
+            last_run_tick,
+        }
+    }
+
+    // Hands the queued commands back to the system manager
+    pub(crate) fn take_commands(&mut self) -> CommandQueue {
+        std::mem::take(&mut self.commands)
+    }
+
+    fn undeclared(&self, what: &str, name: &str) -> ! {
+        panic!("{} {} {} without declaring it in access()", self.system_name, what, name);
+    }
+
+    fn check_query<Q: Query, F: QueryFilter>(&self) {
+        let mut access = Access::default();
+        Q::access(&mut access);
+        F::access(&mut access);
+        for (type_id, name) in access.reads() {
+            if !self.access.can_read_component(*type_id) {
+                self.undeclared("reads", name);
+            }
+        }
+        for (type_id, name) in access.writes() {
+            if !self.access.can_write_component(*type_id) {
+                self.undeclared("writes", name);
+            }
+        }
+    }
+
+    fn check_component_read<T: 'static>(&self) {
+        if !self.access.can_read_component(TypeId::of::<T>()) {
+            self.undeclared("reads", type_name::<T>());
+        }
+    }
+
+    fn check_resource_read<T: 'static>(&self) {
+        if !self.access.can_read_resource(TypeId::of::<T>()) {
+            self.undeclared("reads", type_name::<T>());
+        }
+    }
+
+    fn check_resource_write<T: 'static>(&self) {
+        if !self.access.can_write_resource(TypeId::of::<T>()) {
+            self.undeclared("writes", type_name::<T>());
+        }
+    }
+
+    fn check_main_thread<T: 'static>(&self) {
+        if !self.access.runs_on_main_thread() {
+            panic!("{} uses the non-send resource {} without declaring main_thread", self.system_name, type_name::<T>());
+        }
+    }
+
+    /// Same as `EntityManager::query`
+    pub fn query<Q: Query>(&mut self) -> QueryIter<'_, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+
+    pub fn query_filtered<Q: Query, F: QueryFilter>(&mut self) -> QueryIter<'_, Q> {
+        self.check_query::<Q, F>();
+        // Safety: the access was declared, so no system in the batch borrows these components,
+        // and the iterator holds on to the &mut borrow of the context
+        unsafe { self.entity_manager.query_unchecked::<Q, F>(self.change_tick, self.last_run_tick) }
+    }
+
+    /// Read-only queries only need a shared borrow, so several can be alive at once
+    pub fn query_ref<Q: ReadOnlyQuery>(&self) -> QueryIter<'_, Q> {
+        self.query_ref_filtered::<Q, ()>()
+    }
+
+    pub fn query_ref_filtered<Q: ReadOnlyQuery, F: QueryFilter>(&self) -> QueryIter<'_, Q> {
+        self.check_query::<Q, F>();
+        // Safety: read-only queries never write through a shared borrow
+        unsafe { self.entity_manager.query_unchecked::<Q, F>(self.change_tick, self.last_run_tick) }
+    }
+
+    pub fn is_alive(&self, entity: &Entity) -> bool {
+        self.entity_manager.is_alive(entity)
+    }
+
+    pub fn has_component<T: Component>(&self, entity: &Entity) -> bool {
+        self.check_component_read::<T>();
+        self.entity_manager.has_component::<T>(entity)
+    }
+
+    pub fn get_component<T: Component>(&self, entity: &Entity) -> Option<&T> {
+        self.check_component_read::<T>();
+        self.entity_manager.get_component::<T>(entity)
+    }
+
+    /// Borrows a component mutably, writing through it marks it as changed for the Changed filter
+    pub fn get_component_mut<T: Component>(&mut self, entity: &Entity) -> Option<Mut<'_, T>> {
+        if !self.access.can_write_component(TypeId::of::<T>()) {
+            self.undeclared("writes", type_name::<T>());
+        }
+        // Safety: the write access was declared and the &mut borrow of the context keeps this the only borrow
+        unsafe { self.entity_manager.get_component_unchecked_mut::<T>(entity, self.change_tick) }
+    }
+
+    /// Same as `EntityManager::entity_label`, the name is only included if the system declared it reads Name
+    pub fn entity_label(&self, entity: &Entity) -> String {
+        let name = match self.access.can_read_component(TypeId::of::<Name>()) {
+            true => self.entity_manager.name_of(entity),
+            false => None,
+        };
+        entity_label(name, entity)
+    }
+
+    pub fn resource<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.check_resource_read::<T>();
+        self.entity_manager.resource::<T>()
+    }
+
+    pub fn resource_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.check_resource_write::<T>();
+        // Safety: same as get_component_mut
+        unsafe { self.entity_manager.resource_unchecked_mut::<T>() }
+    }
+
+    pub fn non_send_resource<T: 'static>(&self) -> Option<&T> {
+        self.check_main_thread::<T>();
+        self.check_resource_read::<T>();
+        self.entity_manager.non_send_resource::<T>()
+    }
+
+    pub fn non_send_resource_mut<T: 'static>(&mut self) -> Option<&mut T> {
+        self.check_main_thread::<T>();
+        self.check_resource_write::<T>();
+        // Safety: same as get_component_mut
+        unsafe { self.entity_manager.non_send_resource_unchecked_mut::<T>() }
+    }
+
+    /// Needs access to the `Box<dyn Renderer>` resource and to run on the main thread
+    pub fn renderer(&self) -> Option<&dyn Renderer> {
+        self.non_send_resource::<Box<dyn Renderer>>().map(|renderer| renderer.as_ref())
+    }
+
+    /// Needs write access to the `Events<T>` resource
+    pub fn event_writer<T: Send + Sync + 'static>(&mut self) -> Option<EventWriter<'_, T>> {
+        self.resource_mut::<Events<T>>().map(EventWriter::new)
+    }
+
+    /// Needs read access to the `Events<T>` resource
+    pub fn events<T: Send + Sync + 'static>(&self) -> Option<&Events<T>> {
+        self.resource::<Events<T>>()
+    }
+
+    /// Queues structural changes, they are applied after the batch this system runs in
+    pub fn commands(&mut self) -> Commands<'_> {
+        Commands::new(self.entity_manager.entities(), &mut self.commands)
+    }
+
+    pub fn change_tick(&self) -> u32 {
+        self.change_tick
+    }
+
+    pub fn last_run_tick(&self) -> u32 {
+        self.last_run_tick
+    }
+}
@@ -1,33 +1,78 @@
 // ecs::systems::mod.rs
 // Description:
 // This module contains systems responsible for updating entities and acting on their components. These modules should contain no data unless that data is shared between all entities.
+// There are two kinds of systems:
+//  - System: runs once per frame with a SystemContext and queries whatever it needs (rendering, input).
+//  - EntitySystem: runs once per frame for every entity matching its query, no registration needed.
+// Every system belongs to a Stage, stages run in order and systems inside a stage can ask to run before or after
+// other systems. The Simulation and Physics stages run once per fixed time step, so zero or more times a frame,
+// the others once per frame. The SystemManager works out the final order and refuses orderings that contain a cycle.
+// Systems declare the components and resources they touch, systems that don't conflict run in parallel.
+// A system that can't do its job returns an EngineError, the SystemManager's ErrorPolicy decides what happens next.
+// Systems draw through the Renderer resource, so they run with or without a window. Systems reading the
+// keyboard are only built with the `sdl` feature.
 
+pub mod access;               // Module for declared system access
+pub mod context;              // Module for the context systems run with
 pub mod system_manager;       // Module for system manager
 pub mod movement_system;       // Module for movement system
 pub mod render_system;         // Module for render system
+#[cfg(feature = "sdl")]
 pub mod player_controller;     // Module for player controller
 pub mod collision_system;      // Module for collision system
 pub mod world_system;          // Module for world system
 pub mod camera_system;           // Module for player look system
-use std::any::Any;
-use crate::ecs::entity_manager::EntityManager;
+pub mod transform_propagation_system; // Module for transform propagation system
+use std::any::{Any, TypeId};
+use crate::ecs::entity_manager::Entity;
+use crate::error::EngineError;
+use crate::ecs::query::{Query, QueryFilter};
+use access::SystemAccess;
+use context::SystemContext;
+
+/// The stages of a frame, in the order they run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Stage {
+    Input,       // reading the keyboard and turning it into intent
+    Simulation,  // gameplay and movement, once per fixed step
+    Physics,     // collision response, once per fixed step
+    PostPhysics, // anything that needs final positions for the frame
+    Render,      // drawing into the window
+    Present,     // last touches before the game loop presents the frame
+}
+
+impl Stage {
+    pub const ALL: [Stage; 6] = [
+        Stage::Input,
+        Stage::Simulation,
+        Stage::Physics,
+        Stage::PostPhysics,
+        Stage::Render,
+        Stage::Present,
+    ];
+
+    /// Whether the stage runs once per fixed time step instead of once per frame
+    pub fn is_fixed(&self) -> bool {
+        matches!(self, Stage::Simulation | Stage::Physics)
+    }
+}
 
 #[allow(dead_code)]
 pub trait SystemBase {
-    fn update(&mut self, entity_manager: &mut EntityManager, entity_id: u32);
-    fn priority(&self) -> u32;
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError>;
+    fn stage(&self) -> Stage;
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
 
 // Implementation of SystemBase for any type that implements the System trait.
 impl<T: System + 'static> SystemBase for T {
-    fn update(&mut self, entity_manager: &mut EntityManager, entity_id: u32) {
-        T::update(self, entity_manager, entity_id);
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        T::update(self, context)
     }
 
-    fn priority(&self) -> u32 {
-        T::priority(self)
+    fn stage(&self) -> Stage {
+        T::stage(self)
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -39,10 +84,82 @@ impl<T: System + 'static> SystemBase for T {
     }
 }
 
-// The original System trait.
-pub trait System: Any {
-    fn update(&mut self, entity_manager: &mut EntityManager, entity_id: u32);
-    fn priority(&self) -> u32;
+// The original System trait, update is called exactly once per frame.
+// Systems may run on any thread of the pool, so they have to be Send + Sync.
+pub trait System: Any + Send + Sync {
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError>;
+    fn stage(&self) -> Stage;
+    /// Every component and resource the system touches, e.g.
+    /// `SystemAccess::new().query::<&mut Transform, With<PlayerData>>().read_resource::<WorldData>()`
+    fn access(&self) -> SystemAccess;
+    /// Systems in the same stage that have to run after this one, e.g. `vec![TypeId::of::<WorldSystem>()]`
+    fn before(&self) -> Vec<TypeId> {
+        Vec::new()
+    }
+    /// Systems in the same stage that have to run before this one
+    fn after(&self) -> Vec<TypeId> {
+        Vec::new()
+    }
+    /// Used when reporting the schedule
+    fn name(&self) -> &'static str {
+        short_type_name(std::any::type_name::<Self>())
+    }
     fn as_any(&self) -> &dyn Any;
     fn as_any_mut(&mut self) -> &mut dyn Any;
 }
+
+/// A system that works on one entity at a time. Every entity matching `Query` and `Filter` is passed to
+/// `update_entity` once per frame, e.g. `type Query = &'static mut Transform;`
+/// The access is worked out from the query and filter.
+pub trait EntitySystem: Any + Send + Sync {
+    type Query: Query;
+    type Filter: QueryFilter;
+
+    fn update_entity(&mut self, entity: Entity, item: <Self::Query as Query>::Item<'_>);
+    fn stage(&self) -> Stage;
+    fn before(&self) -> Vec<TypeId> {
+        Vec::new()
+    }
+    fn after(&self) -> Vec<TypeId> {
+        Vec::new()
+    }
+}
+
+// Every EntitySystem is a System that runs its query once and walks the matches.
+impl<T: EntitySystem> System for T {
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        for (entity, item) in context.query_filtered::<(Entity, T::Query), T::Filter>() {
+            self.update_entity(entity, item);
+        }
+        Ok(())
+    }
+
+    fn stage(&self) -> Stage {
+        EntitySystem::stage(self)
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new().query::<T::Query, T::Filter>()
+    }
+
+    fn before(&self) -> Vec<TypeId> {
+        EntitySystem::before(self)
+    }
+
+    fn after(&self) -> Vec<TypeId> {
+        EntitySystem::after(self)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+// Strips the module path, "game::ecs::system::movement_system::MovementSystem" becomes "MovementSystem"
+fn short_type_name(name: &'static str) -> &'static str {
+    name.rsplit("::").next().unwrap_or(name)
+}
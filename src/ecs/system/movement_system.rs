@@ -1,48 +1,63 @@
 // src/ecs/system/movement_system.rs
 // Description:
 // This module contains the movement system, responsible for updating the position of entities based on their velocity.
+// It runs once per fixed step and moves everything by one step's worth of its velocity.
 
-use crate::ecs::entity_manager::{EntityManager, Entity};
-use crate::ecs::system::System;
 use crate::ecs::component::transform_data::Transform;
+use crate::ecs::storage::Mut;
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::ecs::system::{Stage, System};
+use crate::ecs::time::Time;
+use crate::error::EngineError;
 use std::any::Any;
 
 pub struct MovementSystem;
 
 impl MovementSystem {
-    /// Updates the position of a single entity based on its velocity
-    fn update_entity_position(transform: &mut Transform) {
+    /// Moves and turns a single entity by its velocity over `delta` seconds
+    fn update_entity_position(transform: &mut Mut<Transform>, delta: f32) {
+        // Entities at rest are only read, so they don't show up as changed Transforms
+        let velocity = &transform.velocity;
+        if velocity.delta_x == 0.0 && velocity.delta_y == 0.0 && velocity.rotation == 0.0 {
+            return;
+        }
+        let transform: &mut Transform = transform;
         // Calculate the forward vector
         let forward = transform.position.forward_vector();
         //calulate the right vector
         let right = transform.position.right_vector();
         //now calculate the direction it should move relative to looking position and how far it should move using velocity.
-        let delta_x = forward.0 * transform.velocity.delta_x + right.0 * transform.velocity.delta_y * (1.0/60.0);
-        let delta_y = forward.1 * transform.velocity.delta_x + right.1 * transform.velocity.delta_y * (1.0/60.0);
+        let delta_x = (forward.0 * transform.velocity.delta_x + right.0 * transform.velocity.delta_y) * delta;
+        let delta_y = (forward.1 * transform.velocity.delta_x + right.1 * transform.velocity.delta_y) * delta;
         // Update the position
         transform.position.modify_position(delta_x, delta_y);
+        if transform.velocity.rotation != 0.0 {
+            transform.position.update_rotation(transform.velocity.rotation * delta);
+        }
     }
 }
 
 impl System for MovementSystem {
-    fn update(&mut self, entity_manager: &mut EntityManager, _entity_id: u32) {
-        // Collect entity IDs with Transform components first
-        let entities_to_update: Vec<Entity> = entity_manager
-            .query_entities::<Transform>()
-            .iter()
-            .map(|entity| Entity { id: entity.id })
-            .collect();
-
-        // Update positions based on velocities
-        for entity in entities_to_update {
-            if let Some(transform) = entity_manager.get_component_mut::<Transform>(&entity) {
-                MovementSystem::update_entity_position(transform);
-            }
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        let delta = context
+            .resource::<Time>()
+            .ok_or_else(EngineError::missing_resource::<Time>)?
+            .fixed_delta();
+
+        for mut transform in context.query::<&mut Transform>() {
+            // Update position based on velocity
+            MovementSystem::update_entity_position(&mut transform, delta);
         }
+        Ok(())
     }
 
-    fn priority(&self) -> u32 {
-        1
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new().query::<&mut Transform, ()>().read_resource::<Time>()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Simulation
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -53,3 +68,30 @@ impl System for MovementSystem {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::component::transform_data::Velocity;
+    use crate::ecs::entity_manager::{Entity, EntityManager};
+    use crate::ecs::query::Changed;
+
+    #[test]
+    fn transforms_at_rest_are_not_changed() {
+        let mut entity_manager = EntityManager::new();
+        entity_manager.insert_resource(Time::with_fixed_rate(4));
+        entity_manager.add_system(MovementSystem);
+
+        let still = entity_manager.create_entity();
+        entity_manager.add_component(&still, Transform::default());
+        let moving = entity_manager.create_entity();
+        let velocity = Velocity { delta_x: 1.0, ..Default::default() };
+        entity_manager.add_component(&moving, Transform { velocity, ..Default::default() });
+
+        // One fixed step, the MovementSystem borrows both Transforms mutably
+        entity_manager.update_with_delta(0.25).expect("Failed to run the frame");
+
+        let changed: Vec<Entity> = entity_manager.query_filtered::<Entity, Changed<Transform>>().collect();
+        assert_eq!(changed, vec![moving]);
+    }
+}
@@ -1,78 +1,124 @@
 // src/ecs/system/player_controller.rs
 
-use std::rc::Rc;
-use std::cell::RefCell;
-
 use crate::{
-    ecs::entity_manager::{EntityManager, Entity},
+    ecs::system::access::SystemAccess,
+    ecs::system::context::SystemContext,
+    ecs::query::With,
+    error::EngineError,
     input_handler::InputHandler,
-    ecs::system::System,
-    ecs::component::{transform_data::Transform, player_data::PlayerData},
+    ecs::system::{Stage, System},
+    ecs::component::{name::Name, transform_data::{Transform, Velocity}, player_data::PlayerData},
+    ecs::entity_manager::Entity,
+    ecs::storage::Mut,
+    log_debug, log_warn,
 };
 
 use std::any::Any;
 
-pub struct PlayerController {
-    pub input_handler: Rc<RefCell<InputHandler>>, // Expecting Rc<RefCell<InputHandler>>
+pub struct PlayerController;
+
+// Velocity while a movement key is held, the direction vectors are 10 units long so this is 60 pixels a second
+const MOVE_SPEED: f32 = 6.0;
+// Radians per second while a turn key is held, the 0.01 a frame the controller used to turn by at 60 fps
+const TURN_SPEED: f32 = 0.6;
+
+// The keys the controller cares about, read from the InputHandler resource before the player transforms are borrowed
+struct PlayerInput {
+    forward: bool,
+    backward: bool,
+    strafe_left: bool,
+    strafe_right: bool,
+    rotate_counterclockwise: bool,
+    rotate_clockwise: bool,
 }
 
-impl PlayerController {
-    /// Performs the actual task of updating the player's transform based on input
-       /// Performs the actual task of updating the player's transform based on input
-       fn update_player_transform(&self, transform: &mut Transform) {
-        transform.velocity.set_direct(0.0, 0.0); // Reset velocity
+impl PlayerInput {
+    fn read(input_handler: &InputHandler) -> Self {
+        PlayerInput {
+            forward: input_handler.is_w_pressed(),
+            backward: input_handler.is_s_pressed(),
+            strafe_left: input_handler.is_a_pressed(),
+            strafe_right: input_handler.is_d_pressed(),
+            rotate_counterclockwise: input_handler.is_e_pressed(),
+            rotate_clockwise: input_handler.is_q_pressed(),
+        }
+    }
+}
 
-        let input_handler = self.input_handler.borrow();
-        let rotation = transform.position.rotation();
+impl PlayerController {
+    /// Performs the actual task of updating the player's velocity based on input,
+    /// the MovementSystem then moves the player by it every fixed step
+    fn update_player_transform(&self, input: &PlayerInput, transform: &mut Mut<Transform>) {
+        let mut velocity = Velocity::default();
 
-        let speed: f32 = 0.1;
-        if input_handler.is_w_pressed() { // Move forward
-            transform.velocity.delta_x += speed;
+        if input.forward { // Move forward
+            velocity.delta_x += MOVE_SPEED;
         }
-        if input_handler.is_s_pressed() { // Move backward
-            transform.velocity.delta_x -= speed;
+        if input.backward { // Move backward
+            velocity.delta_x -= MOVE_SPEED;
         }
-        if input_handler.is_a_pressed() { // Strafe left
-            transform.velocity.delta_y += speed;
+        if input.strafe_left { // Strafe left
+            velocity.delta_y += MOVE_SPEED;
         }
-        if input_handler.is_d_pressed() { // Strafe right
-            transform.velocity.delta_y -= speed;
+        if input.strafe_right { // Strafe right
+            velocity.delta_y -= MOVE_SPEED;
         }
-        if input_handler.is_e_pressed() { // Rotate counterclockwise
-            transform.position.update_rotation(-0.01);
+        if input.rotate_counterclockwise { // Rotate counterclockwise
+            velocity.rotation -= TURN_SPEED;
         }
-        if input_handler.is_q_pressed() { // Rotate clockwise
-            transform.position.update_rotation(0.01);
+        if input.rotate_clockwise { // Rotate clockwise
+            velocity.rotation += TURN_SPEED;
+        }
+
+        // Only written when the input changed it, so an idle player doesn't count as a changed Transform
+        if transform.velocity != velocity {
+            transform.velocity = velocity;
         }
     }
 }
 
 
 impl System for PlayerController {
-    fn update(&mut self, entity_manager: &mut EntityManager, _entity_id: u32) {
-        let player_entities: Vec<_> = entity_manager
-            .query_entities::<PlayerData>()
-            .into_iter()
-            .map(|entity| entity.id)
-            .collect();
-
-        if player_entities.is_empty() {
-            println!("No player entities");
-            return;
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        let input = PlayerInput::read(
+            context
+                .non_send_resource::<InputHandler>()
+                .ok_or_else(EngineError::missing_resource::<InputHandler>)?,
+        );
+
+        let players = context.query_filtered::<(Entity, Option<&mut Transform>), With<PlayerData>>();
+
+        if players.len() == 0 {
+            log_debug!("No player entities");
+            return Ok(());
         }
 
-        for entity_id in player_entities {
-            if let Some(transform) = entity_manager.get_component_mut::<Transform>(&Entity { id: entity_id }) {
+        let mut without_transform = Vec::new();
+        for (entity, transform) in players {
+            if let Some(mut transform) = transform {
                 // Delegate the update task to the struct implementation
-                self.update_player_transform(transform);
+                self.update_player_transform(&input, &mut transform);
             } else {
-                println!("No transform component for player entity");
+                without_transform.push(entity);
             }
         }
+        for entity in without_transform {
+            log_warn!("No transform component for player entity {}", context.entity_label(&entity));
+        }
+        Ok(())
+    }
+
+    // The input handler wraps the SDL event pump, so this runs on the main thread
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .query::<(Entity, Option<&mut Transform>), With<PlayerData>>()
+            .read::<Name>() // for diagnostics
+            .read_resource::<InputHandler>()
+            .main_thread()
     }
 
-    fn priority(&self) -> u32 {
-        3
+    fn stage(&self) -> Stage {
+        Stage::Input
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -1,24 +1,21 @@
 /// src/ecs/system/render_system.rs
 /// Description:
 /// This module contains the render system which is responsible for rendering entities in the world.
-use sdl2::render;
-use crate::ecs::entity_manager::EntityManager;
-use crate::sdl_window_manager::SDLWindowManager;
-use crate::ecs::component::{transform_data::Transform, render_data::RenderData};
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::error::EngineError;
+use crate::render::Renderer;
+use crate::ecs::component::{transform_data::GlobalTransform, render_data::RenderData};
 use std::any::Any;
-use std::rc::Rc;
-use std::cell::RefCell;
 
-use super::System;
+use super::{Stage, System};
 
-pub struct RenderSystem {
-    pub window_manager: Rc<RefCell<SDLWindowManager>>,
-}
+pub struct RenderSystem;
 
 impl RenderSystem {
-    /// Draws a single entity using its transform and render data
-    fn draw_entity(&self, window_manager: &mut SDLWindowManager, transform: &Transform, render_data: &RenderData) {
-        window_manager.draw_filled_rect(
+    /// Draws a single entity at its world-space position using its render data
+    fn draw_entity(&self, renderer: &dyn Renderer, transform: &GlobalTransform, render_data: &RenderData) {
+        renderer.draw_filled_rect(
             transform.position.x() as i32,
             transform.position.y() as i32,
             render_data.size as u32,
@@ -31,23 +28,29 @@ impl RenderSystem {
 }
 
 impl System for RenderSystem {
-    fn update(&mut self, entity_manager: &mut EntityManager, _entity_id: u32) {
-        let mut window_manager = self.window_manager.borrow_mut();
-
-        window_manager.clear();
-
-        for entity in entity_manager.query_entities::<RenderData>() {
-            if let Some(render_data) = entity_manager.get_component::<RenderData>(entity) {
-                if let Some(transform) = entity_manager.get_component::<Transform>(entity) {
-                    // Delegate the drawing task to a separate method
-                    self.draw_entity(&mut window_manager, transform, render_data);
-                }
-            }
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        // The game loop clears the window before the systems run
+        let renderer = context
+            .renderer()
+            .ok_or_else(EngineError::missing_resource::<Box<dyn Renderer>>)?;
+
+        for (transform, render_data) in context.query_ref::<(&GlobalTransform, &RenderData)>() {
+            // Delegate the drawing task to a separate method
+            self.draw_entity(renderer, transform, render_data);
         }
+        Ok(())
+    }
+
+    // Draws into the window, which only works on the main thread
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .query::<(&GlobalTransform, &RenderData), ()>()
+            .write_resource::<Box<dyn Renderer>>()
+            .main_thread()
     }
 
-    fn priority(&self) -> u32 {
-        100
+    fn stage(&self) -> Stage {
+        Stage::Render
     }
 
     fn as_any(&self) -> &dyn Any {
@@ -1,51 +1,707 @@
+use crate::ecs::commands::CommandQueue;
 use crate::ecs::entity_manager::EntityManager;
-use crate::ecs::system::System;
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::ecs::system::{Stage, System};
+use crate::error::{EngineError, ErrorPolicy};
+use crate::log_error;
 use std::any::TypeId;
-use std::cell::RefCell;
-use std::collections::{HashMap, HashSet};
-use std::rc::Rc;
+use std::collections::HashMap;
+use std::fmt::Write;
 
-// SystemManager struct to manage systems and their associated entities
+// SystemManager struct to manage systems and the order they run in
+#[derive(Default)]
 pub struct SystemManager {
-    // Vector of systems, each wrapped in Rc and RefCell for shared ownership and interior mutability
-    systems: Vec<Rc<RefCell<dyn System>>>,
-    // Registry mapping system TypeIds to sets of entity IDs
-    entity_system_registry: HashMap<TypeId, HashSet<u32>>,
+    // Vector of systems in the order they were added
+    systems: Vec<Box<dyn System>>,
+    // What each system declared it touches, same order as systems
+    accesses: Vec<SystemAccess>,
+    // Groups of indices into systems in the order they run, the systems of one batch run at the same time.
+    // Rebuilt whenever a system is added
+    batches: Vec<Vec<usize>>,
+    schedule_dirty: bool,
+    // Change tick each system last ran at, same order as systems
+    last_run_ticks: Vec<u32>,
+    // What to do when a system fails, per system type with a fallback for the rest (Stop if never set)
+    error_policy: Option<ErrorPolicy>,
+    error_policies: HashMap<TypeId, ErrorPolicy>,
+    // Systems switched off by the Skip policy, same order as systems
+    skipped: Vec<bool>,
 }
 
 impl SystemManager {
-    // Constructor for SystemManager, initializes empty systems vector and registry
+    // Constructor for SystemManager, initializes an empty systems vector
     pub fn new() -> Self {
-        SystemManager {
-            systems: Vec::new(),
-            entity_system_registry: HashMap::new(),
-        }
+        Self::default()
     }
 
-    // Adds a system to the manager and sorts systems by their priority
-    pub fn add_system(&mut self, system: Rc<RefCell<dyn System>>) {
+    // Adds a system to the manager, the schedule is rebuilt before the next update
+    pub fn add_system(&mut self, system: Box<dyn System>) {
+        self.accesses.push(system.access());
         self.systems.push(system);
-        self.systems.sort_by_key(|s| s.borrow().priority());
+        self.last_run_ticks.push(0);
+        self.skipped.push(false);
+        self.schedule_dirty = true;
+    }
+
+    /// What happens when a system without its own policy fails, Stop unless changed
+    pub fn set_error_policy(&mut self, policy: ErrorPolicy) {
+        self.error_policy = Some(policy);
+    }
+
+    /// What happens when the system with this type fails
+    pub fn set_system_error_policy(&mut self, type_id: TypeId, policy: ErrorPolicy) {
+        self.error_policies.insert(type_id, policy);
+    }
+
+    pub fn has_system(&self, type_id: TypeId) -> bool {
+        self.systems.iter().any(|system| system.as_any().type_id() == type_id)
+    }
+
+    pub fn system_types(&self) -> Vec<TypeId> {
+        self.systems.iter().map(|system| system.as_any().type_id()).collect()
+    }
+
+    // Moves every system and policy of the other manager over to this one. The other one is the stand-in
+    // for the frame in progress, so its policies were set last and win
+    pub fn append(&mut self, other: SystemManager) {
+        for system in other.systems {
+            self.add_system(system);
+        }
+        if other.error_policy.is_some() {
+            self.error_policy = other.error_policy;
+        }
+        self.error_policies.extend(other.error_policies);
+    }
+
+    // Works out the run order: stages run in order, inside a stage systems are sorted so every
+    // before/after constraint holds, ties keep the order the systems were added in.
+    // The order is then cut into batches of systems that can run at the same time.
+    // Fails if the constraints contain a cycle or ask a system to run outside of its stage's slot.
+    pub fn build_schedule(&mut self) -> Result<(), String> {
+        let type_ids = self.system_types();
+        let stages: Vec<Stage> = self.systems.iter().map(|system| system.stage()).collect();
+        let index_of = |type_id: &TypeId| type_ids.iter().position(|id| id == type_id);
+
+        // edges[a] holds every system that has to run after system a
+        let mut edges: Vec<Vec<usize>> = vec![Vec::new(); self.systems.len()];
+        for (index, system) in self.systems.iter().enumerate() {
+            // Constraints on systems that were never added are ignored
+            let before = system.before().into_iter().filter_map(|id| index_of(&id)).map(|other| (index, other));
+            let after = system.after().into_iter().filter_map(|id| index_of(&id)).map(|other| (other, index));
+
+            for (first, second) in before.chain(after) {
+                if stages[first] > stages[second] {
+                    return Err(format!(
+                        "{} ({:?}) can't run before {} ({:?}), its stage runs later",
+                        self.system_name(first),
+                        stages[first],
+                        self.system_name(second),
+                        stages[second]
+                    ));
+                }
+                if stages[first] == stages[second] {
+                    edges[first].push(second);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.systems.len());
+        for stage in Stage::ALL {
+            let mut remaining: Vec<usize> = (0..self.systems.len()).filter(|&index| stages[index] == stage).collect();
+
+            // Kahn's algorithm, always taking the earliest added system that has nothing left to wait on
+            while !remaining.is_empty() {
+                let ready = remaining.iter().position(|&candidate| {
+                    !remaining
+                        .iter()
+                        .any(|&other| other != candidate && edges[other].contains(&candidate))
+                });
+
+                match ready {
+                    Some(position) => order.push(remaining.remove(position)),
+                    None => {
+                        let names: Vec<&str> = remaining.iter().map(|&index| self.system_name(index)).collect();
+                        return Err(format!(
+                            "Cycle in the {:?} stage between systems: {}",
+                            stage,
+                            names.join(", ")
+                        ));
+                    }
+                }
+            }
+        }
+
+        // A system joins the batch before it unless it is in another stage, has to wait for a system
+        // in that batch, or touches something one of them writes (or writes something they touch)
+        let mut batches: Vec<Vec<usize>> = Vec::new();
+        for index in order {
+            let joins = batches.last().is_some_and(|batch| {
+                batch.iter().all(|&other| {
+                    stages[other] == stages[index]
+                        && !edges[other].contains(&index)
+                        && self.accesses[other].conflict_with(&self.accesses[index]).is_none()
+                })
+            });
+            match batches.last_mut() {
+                Some(batch) if joins => batch.push(index),
+                _ => batches.push(vec![index]),
+            }
+        }
+
+        self.batches = batches;
+        self.schedule_dirty = false;
+        Ok(())
+    }
+
+    // Lists the resolved order stage by stage, systems sharing a number run at the same time
+    pub fn describe_schedule(&mut self) -> Result<String, String> {
+        if self.schedule_dirty {
+            self.build_schedule()?;
+        }
+
+        let mut description = String::from("System schedule:");
+        let mut current_stage = None;
+        for (position, batch) in self.batches.iter().enumerate() {
+            let stage = self.systems[batch[0]].stage();
+            if current_stage != Some(stage) {
+                let _ = match stage.is_fixed() {
+                    true => write!(description, "\n  {:?} (every fixed step):", stage),
+                    false => write!(description, "\n  {:?}:", stage),
+                };
+                current_stage = Some(stage);
+            }
+
+            let names: Vec<String> = batch
+                .iter()
+                .map(|&index| match self.accesses[index].runs_on_main_thread() {
+                    true => format!("{} (main thread)", self.system_name(index)),
+                    false => self.system_name(index).to_string(),
+                })
+                .collect();
+            let _ = write!(description, "\n    {}. {}", position + 1, names.join(" | "));
+        }
+        Ok(description)
+    }
+
+    fn system_name(&self, index: usize) -> &'static str {
+        self.systems[index].name()
     }
 
-    // Registers an entity to a specific system type
-    pub fn register_entity_to_system<T: System + 'static>(&mut self, entity_id: u32) {
-        let system_type_id = TypeId::of::<T>();
-        self.entity_system_registry
-            .entry(system_type_id)
-            .or_insert_with(HashSet::new)
-            .insert(entity_id);
+    // Runs every system batch by batch, systems find the entities they work on through queries. Systems in the
+    // fixed stages run once per fixed step, the fixed step hooks run before each step, the rest run once.
+    // Commands queued by the systems of a batch are applied as soon as the batch finishes, in schedule order.
+    // Failing systems are handled by their ErrorPolicy, the first failure under Stop is returned once the
+    // frame is done.
+    pub fn update_systems(&mut self, entity_manager: &mut EntityManager, fixed_steps: u32) -> Result<(), EngineError> {
+        if self.schedule_dirty {
+            self.build_schedule().map_err(EngineError::Schedule)?;
+        }
+
+        // Batches are sorted by stage, so the fixed ones sit together in the middle
+        let is_fixed = |batch: &Vec<usize>| self.systems[batch[0]].stage().is_fixed();
+        let fixed_start = self.batches.iter().position(is_fixed).unwrap_or(self.batches.len());
+        let fixed_end = self.batches.iter().rposition(is_fixed).map_or(fixed_start, |position| position + 1);
+
+        let mut stop_error = None;
+        for position in 0..fixed_start {
+            self.run_position(position, entity_manager, &mut stop_error);
+        }
+        for _ in 0..fixed_steps {
+            entity_manager.run_fixed_step_hooks();
+            for position in fixed_start..fixed_end {
+                self.run_position(position, entity_manager, &mut stop_error);
+            }
+        }
+        for position in fixed_end..self.batches.len() {
+            self.run_position(position, entity_manager, &mut stop_error);
+        }
+
+        match stop_error {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    // Runs one batch of the schedule and applies the commands its systems queued
+    fn run_position(&mut self, position: usize, entity_manager: &mut EntityManager, stop_error: &mut Option<EngineError>) {
+        let batch: Vec<usize> = self.batches[position]
+            .iter()
+            .copied()
+            .filter(|&index| !self.skipped[index])
+            .collect();
+        if batch.is_empty() {
+            return;
+        }
+
+        // Every system gets its own tick so it can tell its own changes apart from the others'
+        let ticks: Vec<u32> = batch.iter().map(|_| entity_manager.next_change_tick()).collect();
+        let (mut command_queues, results) = self.run_batch(&batch, &ticks, entity_manager);
+        for (&index, &tick) in batch.iter().zip(&ticks) {
+            self.last_run_ticks[index] = tick;
+        }
+        for (&index, result) in batch.iter().zip(results) {
+            if let Err(error) = result {
+                let error = EngineError::System { system: self.system_name(index), error: Box::new(error) };
+                match self.policy_for(index) {
+                    ErrorPolicy::Log => log_error!("{}", error),
+                    ErrorPolicy::Skip => {
+                        log_error!("{}, it won't run again", error);
+                        self.skipped[index] = true;
+                    }
+                    ErrorPolicy::Stop => {
+                        stop_error.get_or_insert(error);
+                    }
+                }
+            }
+        }
+
+        // Commands applied at the sync point count as changes the systems see on their next run
+        entity_manager.next_change_tick();
+        for commands in &mut command_queues {
+            entity_manager.queue_commands(commands);
+        }
+        entity_manager.apply_commands();
+    }
+
+    fn policy_for(&self, index: usize) -> ErrorPolicy {
+        let type_id = self.systems[index].as_any().type_id();
+        let fallback = self.error_policy.unwrap_or_default();
+        self.error_policies.get(&type_id).copied().unwrap_or(fallback)
     }
 
-    // Updates all systems, passing the entity manager and relevant entity IDs to each system's update method
-    pub fn update_systems(&mut self, entity_manager: &mut EntityManager) {
-        for system in &self.systems {
-            let system_type_id = system.borrow().type_id();
-            if let Some(entity_ids) = self.entity_system_registry.get(&system_type_id) {
-                for &entity_id in entity_ids {
-                    system.borrow_mut().update(entity_manager, entity_id);
+    // Runs the systems of one batch. Systems using non-send resources run on this thread, the others on the
+    // thread pool. Returns the commands each system queued and how it went, in batch order.
+    fn run_batch(
+        &mut self,
+        batch: &[usize],
+        ticks: &[u32],
+        entity_manager: &EntityManager,
+    ) -> (Vec<CommandQueue>, Vec<Result<(), EngineError>>) {
+        let mut contexts: Vec<SystemContext> = batch
+            .iter()
+            .zip(ticks)
+            .map(|(&index, &tick)| {
+                SystemContext::new(
+                    entity_manager,
+                    &self.accesses[index],
+                    self.systems[index].name(),
+                    tick,
+                    self.last_run_ticks[index],
+                )
+            })
+            .collect();
+        let main_thread: Vec<bool> = batch.iter().map(|&index| self.accesses[index].runs_on_main_thread()).collect();
+
+        // Hands out one &mut per system of the batch, in batch order
+        let mut slots: Vec<Option<&mut Box<dyn System>>> = self.systems.iter_mut().map(Some).collect();
+        let mut systems: Vec<&mut Box<dyn System>> = batch
+            .iter()
+            .map(|&index| slots[index].take().expect("System appears twice in a batch"))
+            .collect();
+
+        let mut results: Vec<Result<(), EngineError>> = batch.iter().map(|_| Ok(())).collect();
+        if let ([system], [context], [result]) = (systems.as_mut_slice(), contexts.as_mut_slice(), results.as_mut_slice()) {
+            *result = system.update(context);
+        } else {
+            rayon::in_place_scope(|scope| {
+                let mut on_this_thread = Vec::new();
+                let jobs = systems.into_iter().zip(contexts.iter_mut()).zip(results.iter_mut()).zip(main_thread);
+                for (((system, context), result), main_thread) in jobs {
+                    if main_thread {
+                        on_this_thread.push((system, context, result));
+                    } else {
+                        scope.spawn(move |_| *result = system.update(context));
+                    }
                 }
+                for (system, context, result) in on_this_thread {
+                    *result = system.update(context);
+                }
+            });
+        }
+
+        let command_queues = contexts.iter_mut().map(|context| context.take_commands()).collect();
+        (command_queues, results)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::component::Component;
+    use crate::ecs::query::With;
+    use std::any::{type_name, Any};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+
+    struct Position(u32);
+    impl Component for Position {}
+
+    struct Velocity(u32);
+    impl Component for Velocity {}
+
+    struct Gravity;
+
+    // What a test system does when it runs, nothing if it has none
+    type OnUpdate = dyn Fn(&mut SystemContext) + Send + Sync;
+
+    // Every const parameter is its own system type, the schedule tells systems apart by type
+    struct TestSystem<const ID: usize> {
+        name: &'static str,
+        stage: Stage,
+        before: Vec<TypeId>,
+        after: Vec<TypeId>,
+        access: SystemAccess,
+        on_update: Option<Box<OnUpdate>>,
+        fails: bool,
+    }
+
+    impl<const ID: usize> TestSystem<ID> {
+        fn new(name: &'static str, stage: Stage) -> Self {
+            TestSystem {
+                name,
+                stage,
+                before: Vec::new(),
+                after: Vec::new(),
+                access: SystemAccess::new(),
+                on_update: None,
+                fails: false,
             }
         }
+
+        fn access(mut self, access: SystemAccess) -> Self {
+            self.access = access;
+            self
+        }
+
+        fn on_update(mut self, on_update: impl Fn(&mut SystemContext) + Send + Sync + 'static) -> Self {
+            self.on_update = Some(Box::new(on_update));
+            self
+        }
+
+        // Returns an error from every update, after running on_update
+        fn failing(mut self) -> Self {
+            self.fails = true;
+            self
+        }
+
+        fn before<S: System>(mut self) -> Self {
+            self.before.push(TypeId::of::<S>());
+            self
+        }
+
+        fn after<S: System>(mut self) -> Self {
+            self.after.push(TypeId::of::<S>());
+            self
+        }
+    }
+
+    impl<const ID: usize> System for TestSystem<ID> {
+        fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+            if let Some(on_update) = &self.on_update {
+                on_update(context);
+            }
+            match self.fails {
+                true => Err(EngineError::Load(format!("{} broke", self.name))),
+                false => Ok(()),
+            }
+        }
+
+        fn stage(&self) -> Stage {
+            self.stage
+        }
+
+        fn access(&self) -> SystemAccess {
+            self.access.clone()
+        }
+
+        fn before(&self) -> Vec<TypeId> {
+            self.before.clone()
+        }
+
+        fn after(&self) -> Vec<TypeId> {
+            self.after.clone()
+        }
+
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    fn manager(systems: Vec<Box<dyn System>>) -> SystemManager {
+        let mut manager = SystemManager::new();
+        for system in systems {
+            manager.add_system(system);
+        }
+        manager
+    }
+
+    // The schedule as system names, one inner Vec per batch
+    fn batch_names(manager: &SystemManager) -> Vec<Vec<&'static str>> {
+        manager
+            .batches
+            .iter()
+            .map(|batch| batch.iter().map(|&index| manager.system_name(index)).collect())
+            .collect()
+    }
+
+    fn order(manager: &SystemManager) -> Vec<&'static str> {
+        batch_names(manager).into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn unconstrained_systems_keep_the_order_they_were_added_in() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("c", Stage::Simulation)),
+            Box::new(TestSystem::<1>::new("a", Stage::Simulation)),
+            Box::new(TestSystem::<2>::new("b", Stage::Simulation)),
+        ]);
+        manager.build_schedule().unwrap();
+        assert_eq!(order(&manager), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn stages_run_in_order_whatever_order_systems_were_added_in() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("render", Stage::Render)),
+            Box::new(TestSystem::<1>::new("input", Stage::Input)),
+            Box::new(TestSystem::<2>::new("physics", Stage::Physics)),
+        ]);
+        manager.build_schedule().unwrap();
+        assert_eq!(order(&manager), ["input", "physics", "render"]);
+    }
+
+    #[test]
+    fn before_and_after_reorder_systems_in_a_stage() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("a", Stage::Simulation).after::<TestSystem<2>>()),
+            Box::new(TestSystem::<1>::new("b", Stage::Simulation)),
+            Box::new(TestSystem::<2>::new("c", Stage::Simulation).before::<TestSystem<1>>()),
+        ]);
+        manager.build_schedule().unwrap();
+        // c is the only one without anything to wait on, a and b then keep their insertion order
+        assert_eq!(order(&manager), ["c", "a", "b"]);
+    }
+
+    #[test]
+    fn cycle_is_an_error_naming_the_systems() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("first", Stage::Simulation).before::<TestSystem<1>>()),
+            Box::new(TestSystem::<1>::new("second", Stage::Simulation).before::<TestSystem<0>>()),
+            Box::new(TestSystem::<2>::new("unrelated", Stage::Simulation)),
+        ]);
+        let error = manager.build_schedule().unwrap_err();
+        assert!(error.contains("Cycle in the Simulation stage"), "{}", error);
+        assert!(error.contains("first") && error.contains("second"), "{}", error);
+        assert!(!error.contains("unrelated"), "{}", error);
+    }
+
+    #[test]
+    fn running_before_a_system_in_an_earlier_stage_is_an_error() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("input", Stage::Input)),
+            Box::new(TestSystem::<1>::new("render", Stage::Render).before::<TestSystem<0>>()),
+        ]);
+        let error = manager.build_schedule().unwrap_err();
+        assert!(error.contains("render (Render) can't run before input (Input)"), "{}", error);
+    }
+
+    #[test]
+    fn constraints_across_stages_in_the_right_direction_are_fine() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("render", Stage::Render).after::<TestSystem<1>>()),
+            Box::new(TestSystem::<1>::new("input", Stage::Input)),
+        ]);
+        manager.build_schedule().unwrap();
+        assert_eq!(order(&manager), ["input", "render"]);
+    }
+
+    #[test]
+    fn constraints_on_missing_systems_are_ignored() {
+        let mut manager = manager(vec![Box::new(TestSystem::<0>::new("a", Stage::Simulation).after::<TestSystem<9>>())]);
+        manager.build_schedule().unwrap();
+        assert_eq!(order(&manager), ["a"]);
+    }
+
+    #[test]
+    fn conflicting_systems_never_share_a_batch() {
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("reads position", Stage::Simulation).access(SystemAccess::new().read::<Position>())),
+            Box::new(TestSystem::<1>::new("reads it too", Stage::Simulation).access(SystemAccess::new().read::<Position>())),
+            Box::new(TestSystem::<2>::new("writes position", Stage::Simulation).access(SystemAccess::new().write::<Position>())),
+            Box::new(TestSystem::<3>::new("writes velocity", Stage::Simulation).access(SystemAccess::new().write::<Velocity>())),
+            Box::new(TestSystem::<4>::new("writes gravity", Stage::Simulation).access(SystemAccess::new().write_resource::<Gravity>())),
+            Box::new(TestSystem::<5>::new("reads gravity", Stage::Simulation).access(SystemAccess::new().read_resource::<Gravity>())),
+        ]);
+        manager.build_schedule().unwrap();
+        assert_eq!(
+            batch_names(&manager),
+            [
+                vec!["reads position", "reads it too"],
+                vec!["writes position", "writes velocity", "writes gravity"],
+                vec!["reads gravity"],
+            ]
+        );
+    }
+
+    #[test]
+    fn conflict_names_the_contested_type() {
+        let writer = SystemAccess::new().query::<&mut Position, With<Velocity>>();
+        let reader = SystemAccess::new().read::<Position>();
+
+        assert_eq!(writer.conflict_with(&reader), Some(type_name::<Position>()));
+        assert_eq!(reader.conflict_with(&writer), Some(type_name::<Position>()));
+        assert_eq!(reader.conflict_with(&reader), None);
+        // A filter doesn't borrow anything but still reads the type
+        let velocity_writer = SystemAccess::new().write::<Velocity>();
+        assert_eq!(writer.conflict_with(&velocity_writer), Some(type_name::<Velocity>()));
+    }
+
+    #[test]
+    fn main_thread_systems_run_on_the_calling_thread() {
+        let threads = Arc::new(Mutex::new(Vec::new()));
+        let recorded = threads.clone();
+        let mut manager = manager(vec![
+            Box::new(TestSystem::<0>::new("pooled", Stage::Simulation)),
+            Box::new(
+                TestSystem::<1>::new("main", Stage::Simulation)
+                    .access(SystemAccess::new().main_thread())
+                    .on_update(move |_| recorded.lock().unwrap().push(thread::current().id())),
+            ),
+            Box::new(TestSystem::<2>::new("pooled too", Stage::Simulation)),
+        ]);
+
+        let mut entity_manager = EntityManager::new();
+        manager.update_systems(&mut entity_manager, 1).unwrap();
+        assert_eq!(batch_names(&manager), [["pooled", "main", "pooled too"]]);
+        assert_eq!(*threads.lock().unwrap(), [thread::current().id()]);
+    }
+
+    #[test]
+    fn systems_in_one_batch_write_their_own_components() {
+        let mut entity_manager = EntityManager::new();
+        for _ in 0..100 {
+            let entity = entity_manager.create_entity();
+            entity_manager.add_component(&entity, Position(0));
+            entity_manager.add_component(&entity, Velocity(0));
+        }
+        let mut manager = manager(vec![
+            Box::new(
+                TestSystem::<0>::new("moves", Stage::Simulation)
+                    .access(SystemAccess::new().query::<&mut Position, ()>())
+                    .on_update(|context| context.query::<&mut Position>().for_each(|mut position| position.0 += 1)),
+            ),
+            Box::new(
+                TestSystem::<1>::new("accelerates", Stage::Simulation)
+                    .access(SystemAccess::new().query::<&mut Velocity, ()>())
+                    .on_update(|context| context.query::<&mut Velocity>().for_each(|mut velocity| velocity.0 += 2)),
+            ),
+        ]);
+
+        manager.update_systems(&mut entity_manager, 3).unwrap();
+        assert_eq!(batch_names(&manager), [["moves", "accelerates"]]);
+        for (position, velocity) in entity_manager.query::<(&Position, &Velocity)>() {
+            assert_eq!((position.0, velocity.0), (3, 6));
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "without declaring it in access()")]
+    fn touching_an_undeclared_component_panics() {
+        let mut manager = manager(vec![Box::new(
+            TestSystem::<0>::new("sneaky", Stage::Simulation)
+                .access(SystemAccess::new().read::<Velocity>())
+                .on_update(|context| context.query::<&mut Position>().for_each(|mut position| position.0 += 1)),
+        )]);
+        let mut entity_manager = EntityManager::new();
+        manager.update_systems(&mut entity_manager, 1).unwrap();
+    }
+
+    // The names of the systems that ran, in the order they ran
+    type Ran = Arc<Mutex<Vec<&'static str>>>;
+
+    // Gives out on_update closures recording the system's name whenever it runs
+    fn recorder() -> (Ran, impl Fn(&'static str) -> Box<OnUpdate>) {
+        let ran = Arc::new(Mutex::new(Vec::new()));
+        let recorded = ran.clone();
+        let record = move |name: &'static str| -> Box<OnUpdate> {
+            let recorded = recorded.clone();
+            Box::new(move |_: &mut SystemContext| recorded.lock().unwrap().push(name))
+        };
+        (ran, record)
+    }
+
+    fn recording_manager(policy: ErrorPolicy) -> (SystemManager, Ran) {
+        let (ran, record) = recorder();
+        let failing = TestSystem::<0>::new("failing", Stage::PostPhysics).failing().on_update(record("failing"));
+        let healthy = TestSystem::<1>::new("healthy", Stage::Render).on_update(record("healthy"));
+
+        let mut manager = manager(vec![Box::new(failing), Box::new(healthy)]);
+        manager.set_error_policy(policy);
+        (manager, ran)
+    }
+
+    #[test]
+    fn log_keeps_running_the_failing_system() {
+        let (mut manager, ran) = recording_manager(ErrorPolicy::Log);
+        let mut entity_manager = EntityManager::new();
+        manager.update_systems(&mut entity_manager, 0).unwrap();
+        manager.update_systems(&mut entity_manager, 0).unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["failing", "healthy", "failing", "healthy"]);
+    }
+
+    #[test]
+    fn skip_only_switches_off_the_failing_system() {
+        let (mut manager, ran) = recording_manager(ErrorPolicy::Skip);
+        let mut entity_manager = EntityManager::new();
+        manager.update_systems(&mut entity_manager, 0).unwrap();
+        manager.update_systems(&mut entity_manager, 0).unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["failing", "healthy", "healthy"]);
+    }
+
+    #[test]
+    fn stop_finishes_the_frame_then_returns_the_error() {
+        let (mut manager, ran) = recording_manager(ErrorPolicy::Stop);
+        let mut entity_manager = EntityManager::new();
+        let error = manager.update_systems(&mut entity_manager, 0).unwrap_err();
+        assert!(matches!(error, EngineError::System { system: "failing", .. }), "{}", error);
+        assert_eq!(*ran.lock().unwrap(), ["failing", "healthy"]);
+    }
+
+    #[test]
+    fn system_policy_overrides_the_fallback() {
+        let (mut manager, ran) = recording_manager(ErrorPolicy::Stop);
+        manager.set_system_error_policy(TypeId::of::<TestSystem<0>>(), ErrorPolicy::Skip);
+        let mut entity_manager = EntityManager::new();
+        manager.update_systems(&mut entity_manager, 0).unwrap();
+        manager.update_systems(&mut entity_manager, 0).unwrap();
+        assert_eq!(*ran.lock().unwrap(), ["failing", "healthy", "healthy"]);
+    }
+
+    #[test]
+    fn error_policy_set_during_a_frame_is_kept() {
+        let (ran, record) = recorder();
+        let failing = TestSystem::<0>::new("failing", Stage::Input).failing().on_update(record("failing"));
+        let switch = TestSystem::<1>::new("switch", Stage::Render).on_update(|context| {
+            context.commands().add(|entity_manager| entity_manager.set_error_policy(ErrorPolicy::Skip));
+        });
+
+        let mut entity_manager = EntityManager::new();
+        entity_manager.add_system(failing);
+        entity_manager.add_system(switch);
+
+        // Still Stop in the first frame, the switch only runs after the failing system
+        assert!(entity_manager.update_with_delta(0.0).is_err());
+        entity_manager.update_with_delta(0.0).expect("Skip should have been kept");
+        entity_manager.update_with_delta(0.0).expect("Skip should have been kept");
+        assert_eq!(*ran.lock().unwrap(), ["failing", "failing"]);
     }
 }
@@ -0,0 +1,89 @@
+// src/ecs/system/transform_propagation_system.rs
+// Description:
+// This module contains the transform propagation system. It walks the hierarchy from every root entity down and
+// writes each entity's world-space position into its GlobalTransform, combining the parent's world position with
+// the child's local Transform. It runs after physics so drawing always sees this frame's final positions.
+// Transforms only move in fixed steps, so the local position used is blended between the previous and the current
+// step by how far into the next step the frame is (`Time::alpha`), which keeps motion smooth at any frame rate.
+
+use crate::ecs::component::hierarchy::{Children, Parent};
+use crate::ecs::component::transform_data::{GlobalTransform, Position, PreviousTransform, Transform};
+use crate::ecs::entity_manager::Entity;
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::ecs::query::{With, Without};
+use crate::ecs::time::Time;
+use std::any::Any;
+
+use super::{Stage, System};
+use crate::error::EngineError;
+
+pub struct TransformPropagationSystem;
+
+impl TransformPropagationSystem {
+    /// Updates the entity's GlobalTransform and then those of its children, entities without a Transform
+    /// cut the hierarchy off below them
+    fn propagate(context: &mut SystemContext, entity: Entity, parent: Option<&Position>, alpha: f32) {
+        let current = match context.get_component::<Transform>(&entity) {
+            Some(transform) => &transform.position,
+            None => return,
+        };
+        let local = match context.get_component::<PreviousTransform>(&entity) {
+            Some(previous) => previous.position.lerp(current, alpha),
+            None => current.clone(),
+        };
+        let global = match parent {
+            Some(parent) => parent.compose(&local),
+            None => local,
+        };
+
+        if let Some(mut global_transform) = context.get_component_mut::<GlobalTransform>(&entity) {
+            global_transform.position = global.clone();
+        }
+
+        let children = match context.get_component::<Children>(&entity) {
+            Some(children) => children.iter().copied().collect::<Vec<_>>(),
+            None => return,
+        };
+        for child in children {
+            Self::propagate(context, child, Some(&global), alpha);
+        }
+    }
+}
+
+impl System for TransformPropagationSystem {
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
+        let roots: Vec<Entity> = context
+            .query_filtered::<Entity, (With<Transform>, Without<Parent>)>()
+            .collect();
+
+        // Without a Time resource nothing is interpolated
+        let alpha = context.resource::<Time>().map_or(1.0, Time::alpha);
+        for root in roots {
+            Self::propagate(context, root, None, alpha);
+        }
+        Ok(())
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .read::<Transform>()
+            .read::<PreviousTransform>()
+            .read::<Parent>()
+            .read::<Children>()
+            .write::<GlobalTransform>()
+            .read_resource::<Time>()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::PostPhysics
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
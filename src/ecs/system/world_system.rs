@@ -3,27 +3,26 @@
 /// This module contains the world system which is responsible for rendering the walls in the world.
 /// WARNING: This code is obselotete unless a top-down view is needed.
 use crate::ecs::component::world_data::{Wall, WorldData};
-use crate::ecs::entity_manager::EntityManager;
-use crate::sdl_window_manager::SDLWindowManager;
-use std::any::Any;
-use std::cell::RefCell;
-use std::rc::Rc;
+use crate::ecs::system::access::SystemAccess;
+use crate::ecs::system::context::SystemContext;
+use crate::error::EngineError;
+use crate::log_trace;
+use crate::render::Renderer;
+use std::any::{Any, TypeId};
 
-use super::System;
+use super::render_system::RenderSystem;
+use super::{Stage, System};
 
-pub struct WorldSystem {
-    pub window_manager: Rc<RefCell<SDLWindowManager>>,
-}
+pub struct WorldSystem;
 
 impl WorldSystem {
     /// Draws all walls for the world
-    fn draw_walls(&self, walls: &[Wall], cell_size: u32) {
-        print!("Drawing walls..."); // Debug print
-        let sdl_window_manager = self.window_manager.borrow_mut();
+    fn draw_walls(&self, renderer: &dyn Renderer, walls: &[Wall], cell_size: u32) {
+        log_trace!("Drawing {} walls", walls.len());
 
         for wall in walls {
 
-            sdl_window_manager.draw_filled_rect(
+            renderer.draw_filled_rect(
                 (wall.x * cell_size) as i32,
                 (wall.y * cell_size) as i32,
                 cell_size,
@@ -34,7 +33,7 @@ impl WorldSystem {
             );
 
             // Outline via non-filled rect
-            sdl_window_manager.draw_rect(
+            renderer.draw_rect(
                 (wall.x * cell_size) as i32,
                 (wall.y * cell_size) as i32,
                 cell_size,
@@ -48,31 +47,38 @@ impl WorldSystem {
 }
 
 impl System for WorldSystem {
-    fn update(&mut self, entity_manager: &mut EntityManager, _entity_id: u32) {
-        let world_entities = entity_manager.query_entities::<WorldData>();
-
-        // Ensure there's exactly one world entity
-        match world_entities.len() {
-            0 => panic!("There must be a world data component"),
-            1 => (),
-            _ => panic!("There can only be one world data component"),
-        }
-
+    fn update(&mut self, context: &mut SystemContext) -> Result<(), EngineError> {
         // Get the world data
-        let world_data = entity_manager
-            .get_component::<WorldData>(&world_entities[0])
-            .expect("Failed to get WorldData component");
+        let world_data = context
+            .resource::<WorldData>()
+            .ok_or_else(EngineError::missing_resource::<WorldData>)?;
+        let renderer = context
+            .renderer()
+            .ok_or_else(EngineError::missing_resource::<Box<dyn Renderer>>)?;
 
         // Get all the walls to be rendered
         let walls = world_data.get_all_walls();
         let cell_size = world_data.get_cell_size();
 
         // Delegate the wall drawing to a separate method
-        self.draw_walls(&walls, cell_size);
+        self.draw_walls(renderer, &walls, cell_size);
+        Ok(())
+    }
+
+    fn access(&self) -> SystemAccess {
+        SystemAccess::new()
+            .read_resource::<WorldData>()
+            .write_resource::<Box<dyn Renderer>>()
+            .main_thread()
+    }
+
+    fn stage(&self) -> Stage {
+        Stage::Render
     }
 
-    fn priority(&self) -> u32 {
-        110
+    // Walls are drawn over the entity markers
+    fn after(&self) -> Vec<TypeId> {
+        vec![TypeId::of::<RenderSystem>()]
     }
 
     fn as_any(&self) -> &dyn Any {
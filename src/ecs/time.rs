@@ -0,0 +1,142 @@
+// src/ecs/time.rs
+// Description:
+// This module contains the Time resource. Every frame the entity manager measures how long the last frame took
+// and works out how many fixed simulation steps fit into it. Systems in the fixed stages (Simulation, Physics)
+// run once per step and move things by `fixed_delta`, so movement speed doesn't depend on the frame rate.
+// The time left over is kept for the next frame, `alpha` says how far rendering is between the last two steps
+// so drawing can interpolate instead of stuttering.
+
+use std::time::Instant;
+
+/// Frames longer than this are cut short so a hitch doesn't make the simulation run hundreds of steps
+const MAX_DELTA: f32 = 0.25;
+
+pub struct Time {
+    delta: f32,        // seconds the last frame took
+    elapsed: f32,      // seconds since the first frame
+    fixed_delta: f32,  // seconds one simulation step covers
+    accumulator: f32,  // seconds not yet simulated
+    alpha: f32,        // how far between the previous and the current step rendering is, 0 to 1
+    frame: u64,
+    last_update: Option<Instant>,
+}
+
+impl Time {
+    /// 60 simulation steps a second
+    pub fn new() -> Self {
+        Self::with_fixed_rate(60)
+    }
+
+    pub fn with_fixed_rate(steps_per_second: u32) -> Self {
+        Time {
+            delta: 0.0,
+            elapsed: 0.0,
+            fixed_delta: 1.0 / steps_per_second.max(1) as f32,
+            accumulator: 0.0,
+            alpha: 0.0,
+            frame: 0,
+            last_update: None,
+        }
+    }
+
+    /// Seconds the last frame took, for anything that runs once per frame
+    pub fn delta(&self) -> f32 {
+        self.delta
+    }
+
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Seconds one simulation step covers, for systems in the fixed stages
+    pub fn fixed_delta(&self) -> f32 {
+        self.fixed_delta
+    }
+
+    /// How far between the previous and the current simulation step this frame is drawn, 0 to 1
+    pub fn alpha(&self) -> f32 {
+        self.alpha
+    }
+
+    /// Number of frames so far
+    pub fn frame(&self) -> u64 {
+        self.frame
+    }
+
+    pub fn set_fixed_rate(&mut self, steps_per_second: u32) {
+        self.fixed_delta = 1.0 / steps_per_second.max(1) as f32;
+    }
+
+    // Seconds since the last call, the very first frame counts as one simulation step
+    pub(crate) fn measure(&mut self) -> f32 {
+        let now = Instant::now();
+        let delta = match self.last_update {
+            Some(last_update) => now.duration_since(last_update).as_secs_f32(),
+            None => self.fixed_delta,
+        };
+        self.last_update = Some(now);
+        delta
+    }
+
+    // Starts a frame of the given length, returns how many simulation steps to run
+    pub(crate) fn advance(&mut self, delta: f32) -> u32 {
+        let delta = delta.clamp(0.0, MAX_DELTA);
+        self.delta = delta;
+        self.elapsed += delta;
+        self.frame += 1;
+
+        self.accumulator += delta;
+        let steps = (self.accumulator / self.fixed_delta).floor();
+        self.accumulator -= steps * self.fixed_delta;
+        self.alpha = (self.accumulator / self.fixed_delta).clamp(0.0, 1.0);
+        steps as u32
+    }
+}
+
+impl Default for Time {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn advance_runs_every_step_that_fits() {
+        // Quarter second steps, so all the sums below are exact
+        let mut time = Time::with_fixed_rate(4);
+        assert_eq!(time.advance(0.125), 0);
+        assert_eq!(time.advance(0.125), 1);
+        assert_eq!(time.advance(0.25), 1);
+        assert_eq!(time.advance(0.0), 0);
+        assert_eq!(time.advance(0.1875), 0);
+        assert_eq!(time.advance(0.1875), 1);
+        assert_eq!(time.frame(), 6);
+        assert_eq!(time.elapsed(), 0.875);
+    }
+
+    #[test]
+    fn long_frames_are_clamped_to_max_delta() {
+        let mut time = Time::with_fixed_rate(8);
+        assert_eq!(time.advance(10.0), 2);
+        assert_eq!(time.delta(), MAX_DELTA);
+        assert_eq!(time.elapsed(), MAX_DELTA);
+
+        // A clock going backwards counts as no time at all
+        assert_eq!(time.advance(-1.0), 0);
+        assert_eq!(time.delta(), 0.0);
+    }
+
+    #[test]
+    fn alpha_is_the_leftover_fraction_of_a_step() {
+        let mut time = Time::with_fixed_rate(4);
+        time.advance(0.0625);
+        assert_eq!(time.alpha(), 0.25);
+        assert_eq!(time.advance(0.125), 0);
+        assert_eq!(time.alpha(), 0.75);
+        assert_eq!(time.advance(0.0625), 1);
+        assert_eq!(time.alpha(), 0.0);
+    }
+}
@@ -0,0 +1,78 @@
+// src/error.rs
+// Description:
+// This module contains EngineError, the error type shared by the whole engine. Systems return it from `update`
+// instead of panicking, the SystemManager then decides what to do with it based on the ErrorPolicy.
+
+use std::any::type_name;
+use std::fmt;
+
+#[derive(Debug)]
+pub enum EngineError {
+    /// An SDL call failed
+    Sdl(String),
+    /// A resource the code relies on was never inserted
+    MissingResource(&'static str),
+    /// An entity is missing a component the code relies on
+    MissingComponent { entity: String, component: &'static str },
+    /// A query found a different number of entities than the code can work with
+    UnexpectedEntityCount { what: &'static str, expected: usize, found: usize },
+    /// The system ordering constraints can't be satisfied
+    Schedule(String),
+    /// Loading or saving prefabs, snapshots or other files failed
+    Load(String),
+    /// A system failed, wraps what went wrong
+    System { system: &'static str, error: Box<EngineError> },
+}
+
+impl EngineError {
+    pub fn missing_resource<T: 'static>() -> Self {
+        EngineError::MissingResource(short_type_name::<T>())
+    }
+
+    pub fn missing_component<T: 'static>(entity: impl Into<String>) -> Self {
+        EngineError::MissingComponent { entity: entity.into(), component: short_type_name::<T>() }
+    }
+}
+
+impl fmt::Display for EngineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EngineError::Sdl(error) => write!(f, "SDL error: {}", error),
+            EngineError::MissingResource(resource) => write!(f, "The {} resource is missing", resource),
+            EngineError::MissingComponent { entity, component } => write!(f, "{} has no {}", entity, component),
+            EngineError::UnexpectedEntityCount { what, expected, found } => {
+                write!(f, "Expected {} {}, found {}", expected, what, found)
+            }
+            EngineError::Schedule(error) => write!(f, "Failed to build the system schedule: {}", error),
+            EngineError::Load(error) => f.write_str(error),
+            EngineError::System { system, error } => write!(f, "{} failed: {}", system, error),
+        }
+    }
+}
+
+impl std::error::Error for EngineError {}
+
+// Loading code builds its messages as strings, `?` turns them into Load errors
+impl From<String> for EngineError {
+    fn from(error: String) -> Self {
+        EngineError::Load(error)
+    }
+}
+
+/// What the SystemManager does when a system returns an error
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Report the error and keep running the system every frame
+    Log,
+    /// Report the error once and stop running the system
+    Skip,
+    /// Finish the frame, then hand the error to the game loop so it can stop
+    #[default]
+    Stop,
+}
+
+// "game::ecs::component::world_data::WorldData" becomes "WorldData"
+fn short_type_name<T: 'static>() -> &'static str {
+    let name = type_name::<T>();
+    name.rsplit("::").next().unwrap_or(name)
+}
@@ -1,77 +1,269 @@
+use crate::app::App;
 use crate::ecs::entity_manager::EntityManager;
+use crate::error::EngineError;
 use crate::input_handler::InputHandler;
 use crate::level::level::Level;
-use crate::sdl_window_manager::SDLWindowManager;
+use crate::render::Renderer;
+use crate::{log_debug, log_error, log_info, log_warn};
 use sdl2::keyboard::Keycode;
-use std::cell::RefCell;
-use std::rc::Rc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+const QUICK_SAVE_PATH: &str = "saves/quicksave.json";
+const STATS_INTERVAL: Duration = Duration::from_secs(1);
+
+// Frame timings gathered over STATS_INTERVAL and logged at the debug level, run with GAME_LOG=debug to see them
+struct FrameStats {
+    since: Instant,
+    frames: u32,
+    busy: Duration, // time spent on frames, without the wait for the target frame rate
+    slowest: Duration,
+}
+
+impl FrameStats {
+    fn new() -> Self {
+        FrameStats { since: Instant::now(), frames: 0, busy: Duration::ZERO, slowest: Duration::ZERO }
+    }
+
+    fn record(&mut self, frame_time: Duration, entity_count: usize) {
+        self.frames += 1;
+        self.busy += frame_time;
+        self.slowest = self.slowest.max(frame_time);
+
+        let elapsed = self.since.elapsed();
+        if elapsed >= STATS_INTERVAL {
+            log_debug!(
+                "{:.1} fps, {:.2} ms a frame on average, {:.2} ms slowest, {} entities",
+                self.frames as f32 / elapsed.as_secs_f32(),
+                self.busy.as_secs_f32() * 1000.0 / self.frames as f32,
+                self.slowest.as_secs_f32() * 1000.0,
+                entity_count
+            );
+            *self = FrameStats::new();
+        }
+    }
+}
 
 pub struct GameManager {
-    pub input_handler: Rc<RefCell<InputHandler>>,
-    pub window_manager: Rc<RefCell<SDLWindowManager>>,
-    pub entity_manager: Option<EntityManager>,
+    pub entity_manager: EntityManager,
+    save_key_held: bool, // so holding F5/F9 only saves or loads once
+    load_key_held: bool,
+    error: Option<EngineError>, // set when a system stops the game, the error screen is shown instead
+    title: String,              // window title to go back to once the error is cleared
+    target_frame_rate: Option<u32>, // frames per second the loop is held to, None runs as fast as possible
+    stats: FrameStats,
 }
 
 impl GameManager {
-    pub fn new(input_handler: InputHandler, window_manager: SDLWindowManager) -> Rc<RefCell<Self>> {
-        let input_handler_rc = Rc::new(RefCell::new(input_handler));
-        let window_manager_rc = Rc::new(RefCell::new(window_manager));
-
-        // First, create a GameManager without the EntityManager
-        let game_manager_rc = Rc::new(RefCell::new(GameManager {
-            input_handler: Rc::clone(&input_handler_rc),
-            window_manager: Rc::clone(&window_manager_rc),
-            entity_manager: None, // Start with None
-        }));
-
-        // Now, create the EntityManager and assign it to the GameManager
-        let entity_manager = EntityManager::new(Rc::clone(&game_manager_rc));
-        game_manager_rc.borrow_mut().entity_manager = Some(entity_manager);
-
-        // Return the Rc<RefCell<GameManager>>
-        game_manager_rc
+    // The app must have been given the InputHandler as a non-send resource and a renderer, usually the
+    // SDLWindowManager. Both wrap SDL handles that have to stay on the main thread
+    pub fn new(app: App) -> Result<Self, EngineError> {
+        let entity_manager = app.build()?;
+        if !entity_manager.has_non_send_resource::<InputHandler>() {
+            return Err(EngineError::missing_resource::<InputHandler>());
+        }
+        if !entity_manager.has_non_send_resource::<Box<dyn Renderer>>() {
+            return Err(EngineError::missing_resource::<Box<dyn Renderer>>());
+        }
+
+        Ok(GameManager {
+            entity_manager,
+            save_key_held: false,
+            load_key_held: false,
+            error: None,
+            title: String::new(),
+            target_frame_rate: Some(60),
+            stats: FrameStats::new(),
+        })
+    }
+
+    /// Frames per second the game loop waits for, 60 unless changed. None draws as fast as possible.
+    /// The simulation runs at the rate of the Time resource either way.
+    pub fn set_target_frame_rate(&mut self, frames_per_second: Option<u32>) {
+        self.target_frame_rate = frames_per_second;
     }
 
     pub fn get_entity_manager_mut(&mut self) -> &mut EntityManager {
-        self.entity_manager
-            .as_mut()
-            .expect("EntityManager is not initialized")
+        &mut self.entity_manager
     }
 
-    pub fn initialize_level(&mut self) {
-        let input_handler = Rc::clone(&self.input_handler);
-        let window_manager = Rc::clone(&self.window_manager);
+    pub fn initialize_level(&mut self) -> Result<(), EngineError> {
+        // Load the level (entities and systems), a level that can't be loaded shows the error screen
+        // once the game loop runs instead of aborting
+        let window_size = self.renderer().get_window_size();
+        if let Err(error) = Level::load(self.get_entity_manager_mut(), window_size) {
+            self.show_error(error);
+        }
 
-        // Load the level (entities and systems)
-        Level::load(self.get_entity_manager_mut(), input_handler, window_manager);
+        // Report the order the systems will run in, this also catches ordering cycles before the first frame
+        log_info!("{}", self.entity_manager.describe_schedule()?);
+        Ok(())
     }
 
     pub fn run_game_loop(&mut self) {
         'running: loop {
-            std::process::Command::new("cmd")
-            .args(&["/C", "cls"])
-            .status()
-            .expect("Failed to clear terminal");
-    
+            let frame_start = Instant::now();
+
             //clear window
-            {
-                self.window_manager.borrow_mut().clear();
-            }
-            self.input_handler.borrow_mut().update();
+            self.renderer().clear();
+
+            let input_handler = self.input_handler();
+            input_handler.update();
 
-            if self.input_handler.borrow().is_key_down(Keycode::Escape) {
+            if input_handler.is_key_down(Keycode::Escape) {
                 break 'running; // Exit the game loop if Escape is pressed
             }
 
-            self.get_entity_manager_mut().update(); // Update all systems through the entity manager
+            // F5 quick saves, F9 loads the quick save
+            let save_key_down = input_handler.is_key_down(Keycode::F5);
+            let load_key_down = input_handler.is_key_down(Keycode::F9);
+            if save_key_down && !self.save_key_held {
+                if let Err(error) = self.entity_manager.save_world(QUICK_SAVE_PATH) {
+                    log_error!("Quick save failed: {}", error);
+                }
+            }
+            if load_key_down && !self.load_key_held {
+                match self.entity_manager.load_world(QUICK_SAVE_PATH) {
+                    Ok(()) => self.clear_error(),
+                    Err(error) => log_error!("Quick load failed: {}", error),
+                }
+            }
+            self.save_key_held = save_key_down;
+            self.load_key_held = load_key_down;
+
+            self.update_frame();
 
             //present the window
-            {
-                self.window_manager.borrow_mut().present();
+            self.renderer().present();
+
+            self.stats.record(frame_start.elapsed(), self.entity_manager.entity_count());
+
+            // Wait out the rest of the frame to hold the target frame rate
+            if let Some(frames_per_second) = self.target_frame_rate.filter(|&fps| fps > 0) {
+                let frame_time = Duration::from_secs_f64(1.0 / frames_per_second as f64);
+                if let Some(remaining) = frame_time.checked_sub(frame_start.elapsed()) {
+                    std::thread::sleep(remaining);
+                }
+            }
+        }
+    }
+
+    // Updates all systems through the entity manager, a failing system under the Stop policy switches to the
+    // error screen, which is drawn instead from then on
+    fn update_frame(&mut self) {
+        match &self.error {
+            Some(_) => self.draw_error_screen(),
+            None => {
+                if let Err(error) = self.get_entity_manager_mut().update() {
+                    self.show_error(error);
+                }
+            }
+        }
+    }
+
+    // Stops running the systems and shows the error screen until the player quits or loads the quick save
+    fn show_error(&mut self, error: EngineError) {
+        log_error!("{}", error);
+        self.title = self.renderer().title();
+        if let Err(title_error) = self.renderer().set_title(&format!("Error: {}", error)) {
+            log_warn!("{}", title_error);
+        }
+        self.error = Some(error);
+    }
+
+    fn clear_error(&mut self) {
+        if self.error.take().is_some() {
+            if let Err(title_error) = self.renderer().set_title(&self.title) {
+                log_warn!("{}", title_error);
             }
-            // Sleep to limit frame rate to 60 FPS
-            std::thread::sleep(Duration::from_millis(6));
         }
     }
+
+    // A dark red screen with a warning sign, the message itself is in the title bar and on the console
+    fn draw_error_screen(&self) {
+        let renderer = self.renderer();
+        let (width, height) = renderer.get_window_size();
+        renderer.draw_filled_rect(0, 0, width, height, 90, 10, 10);
+
+        let (center_x, center_y) = ((width / 2) as i32, (height / 2) as i32);
+        let unit = (width.min(height) / 40).max(1);
+        renderer.draw_filled_rect(center_x - unit as i32 / 2, center_y - 5 * unit as i32, unit, 6 * unit, 255, 255, 255);
+        renderer.draw_filled_rect(center_x - unit as i32 / 2, center_y + 2 * unit as i32, unit, unit, 255, 255, 255);
+    }
+
+    fn input_handler(&self) -> &InputHandler {
+        self.entity_manager
+            .non_send_resource::<InputHandler>()
+            .expect("InputHandler resource is not initialized")
+    }
+
+    fn renderer(&self) -> &dyn Renderer {
+        self.entity_manager.renderer().expect("Renderer resource is not initialized")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ecs::system::access::SystemAccess;
+    use crate::ecs::system::context::SystemContext;
+    use crate::ecs::system::{Stage, System};
+    use crate::render::frame_buffer::FrameBuffer;
+    use std::any::Any;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::sync::Arc;
+
+    struct Broken(Arc<AtomicU32>);
+
+    impl System for Broken {
+        fn update(&mut self, _context: &mut SystemContext) -> Result<(), EngineError> {
+            self.0.fetch_add(1, Ordering::Relaxed);
+            Err(EngineError::Load("broken".to_string()))
+        }
+
+        fn stage(&self) -> Stage {
+            Stage::PostPhysics
+        }
+
+        fn access(&self) -> SystemAccess {
+            SystemAccess::new()
+        }
+
+        fn as_any(&self) -> &dyn Any {
+            self
+        }
+
+        fn as_any_mut(&mut self) -> &mut dyn Any {
+            self
+        }
+    }
+
+    #[test]
+    fn stop_halts_the_systems_and_shows_the_error_screen() {
+        let runs = Arc::new(AtomicU32::new(0));
+        let mut entity_manager = EntityManager::new();
+        entity_manager.insert_renderer(FrameBuffer::new(40, 30));
+        entity_manager.add_system(Broken(runs.clone()));
+
+        // Built by hand, new wants an SDL InputHandler and update_frame doesn't read input
+        let mut game_manager = GameManager {
+            entity_manager,
+            save_key_held: false,
+            load_key_held: false,
+            error: None,
+            title: String::new(),
+            target_frame_rate: None,
+            stats: FrameStats::new(),
+        };
+
+        game_manager.update_frame();
+        assert!(matches!(game_manager.error, Some(EngineError::System { system: "Broken", .. })));
+
+        game_manager.update_frame();
+        game_manager.update_frame();
+        assert_eq!(runs.load(Ordering::Relaxed), 1);
+
+        let frame_buffer = game_manager.renderer().as_any().downcast_ref::<FrameBuffer>().unwrap();
+        assert_eq!(frame_buffer.get_pixel(0, 0), Some([90, 10, 10, 255]));
+        assert_eq!(frame_buffer.get_pixel(20, 15), Some([255, 255, 255, 255]));
+    }
 }
@@ -0,0 +1,21 @@
+// src/lib.rs
+// Description:
+// The engine as a library: the ECS, plugins, prefabs and levels, plus the SDL window and input behind the
+// `sdl` feature (on by default). A game depends on this crate, puts an App together from plugins and hands it
+// to the GameManager, see examples/demo.rs. Without the `sdl` feature the ECS core builds and tests headless,
+// rendering into a FrameBuffer instead of a window.
+
+pub mod app;
+pub mod ecs;
+pub mod error;
+pub mod level;
+pub mod log;
+pub mod plugins;
+pub mod prefabs;
+pub mod render;
+#[cfg(feature = "sdl")]
+pub mod game_manager;
+#[cfg(feature = "sdl")]
+pub mod input_handler;
+#[cfg(feature = "sdl")]
+pub mod sdl_window_manager;
@@ -0,0 +1,156 @@
+// src/log.rs
+// Description:
+// This module contains the engine's logger. Messages have a level and are tagged with the module they came from,
+// anything below the maximum level is dropped before it is formatted. The maximum level starts out as Info and
+// can be set with the GAME_LOG environment variable (error, warn, info, debug or trace) or `set_max_level`.
+// Messages go to stderr unless a different output is set, e.g. to show them in game or to check them in tests.
+//   log_warn!("No player entities");
+//   log_debug!("{} fps", fps);
+
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::{Once, RwLock};
+use std::time::Instant;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace,
+}
+
+impl Level {
+    fn from_u8(level: u8) -> Level {
+        match level {
+            1 => Level::Error,
+            2 => Level::Warn,
+            3 => Level::Info,
+            4 => Level::Debug,
+            _ => Level::Trace,
+        }
+    }
+
+    /// Parses the names used by GAME_LOG, ignoring case
+    pub fn parse(name: &str) -> Option<Level> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        };
+        f.pad(name)
+    }
+}
+
+/// Where messages end up: the level, the module that logged it and the message
+pub type Output = Box<dyn Fn(Level, &str, &fmt::Arguments) + Send + Sync>;
+
+static MAX_LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+static READ_ENVIRONMENT: Once = Once::new();
+static OUTPUT: RwLock<Option<Output>> = RwLock::new(None);
+static START: RwLock<Option<Instant>> = RwLock::new(None);
+
+// GAME_LOG is only looked at once, before the first message, so set_max_level wins over it
+fn read_environment() {
+    READ_ENVIRONMENT.call_once(|| {
+        if let Some(level) = std::env::var("GAME_LOG").ok().as_deref().and_then(Level::parse) {
+            MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+        }
+    });
+}
+
+pub fn set_max_level(level: Level) {
+    read_environment();
+    MAX_LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+pub fn max_level() -> Level {
+    read_environment();
+    Level::from_u8(MAX_LEVEL.load(Ordering::Relaxed))
+}
+
+pub fn enabled(level: Level) -> bool {
+    level <= max_level()
+}
+
+/// Sends every message to `output` instead of stderr
+pub fn set_output(output: impl Fn(Level, &str, &fmt::Arguments) + Send + Sync + 'static) {
+    *OUTPUT.write().unwrap_or_else(|error| error.into_inner()) = Some(Box::new(output));
+}
+
+/// Goes back to writing to stderr
+pub fn reset_output() {
+    *OUTPUT.write().unwrap_or_else(|error| error.into_inner()) = None;
+}
+
+// Seconds since the first message, so lines can be lined up with each other
+fn uptime() -> f32 {
+    if let Some(start) = *START.read().unwrap_or_else(|error| error.into_inner()) {
+        return start.elapsed().as_secs_f32();
+    }
+    START.write().unwrap_or_else(|error| error.into_inner()).get_or_insert_with(Instant::now);
+    0.0
+}
+
+/// Used by the log macros, which check the level first
+pub fn write(level: Level, module: &str, message: fmt::Arguments) {
+    if !enabled(level) {
+        return;
+    }
+    // "game::ecs::system::system_manager" is shortened to "system_manager"
+    let module = module.rsplit("::").next().unwrap_or(module);
+    match OUTPUT.read().unwrap_or_else(|error| error.into_inner()).as_ref() {
+        Some(output) => output(level, module, &message),
+        None => eprintln!("[{:>9.3}s {:<5} {}] {}", uptime(), level, module, message),
+    }
+}
+
+#[macro_export]
+macro_rules! log_at {
+    ($level:expr, $($arg:tt)+) => {
+        if $crate::log::enabled($level) {
+            $crate::log::write($level, module_path!(), format_args!($($arg)+));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! log_error {
+    ($($arg:tt)+) => { $crate::log_at!($crate::log::Level::Error, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_warn {
+    ($($arg:tt)+) => { $crate::log_at!($crate::log::Level::Warn, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_info {
+    ($($arg:tt)+) => { $crate::log_at!($crate::log::Level::Info, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_debug {
+    ($($arg:tt)+) => { $crate::log_at!($crate::log::Level::Debug, $($arg)+) };
+}
+
+#[macro_export]
+macro_rules! log_trace {
+    ($($arg:tt)+) => { $crate::log_at!($crate::log::Level::Trace, $($arg)+) };
+}
@@ -0,0 +1,36 @@
+// src/plugins/core.rs
+// Description: The CorePlugin registers what every game built on the engine needs: transforms, collisions,
+// players and the world map. It doesn't need SDL, so it also works for tests and tools.
+
+use crate::app::{App, Plugin};
+use crate::ecs::component::{
+    camera_data::CameraData, collision_data::CollisionData, player_data::PlayerData, render_data::RenderData, sprite::Sprite, tags::Pickup,
+    transform_data::Transform,
+    world_data::WorldData,
+};
+use crate::ecs::system::{
+    collision_system::{CollisionEvent, CollisionSystem}, movement_system::MovementSystem,
+    transform_propagation_system::TransformPropagationSystem,
+};
+use crate::prefabs::player::Player;
+
+pub struct CorePlugin;
+
+impl Plugin for CorePlugin {
+    fn build(&self, app: &mut App) {
+        // GlobalTransform is left out of save and prefab files as it is rebuilt every frame
+        app.add_event::<CollisionEvent>()
+            .register_component::<Transform>("Transform")
+            .register_component::<RenderData>("RenderData")
+            .register_component::<Sprite>("Sprite")
+            .register_component::<CollisionData>("CollisionData")
+            .register_component::<Pickup>("Pickup")
+            .register_resource::<WorldData>("WorldData")
+            .add_system(TransformPropagationSystem)
+            .register_prefab_system("MovementSystem", || MovementSystem)
+            .register_prefab_system("CollisionSystem", || CollisionSystem)
+            .register_component::<PlayerData>("PlayerData")
+            .register_component::<CameraData>("CameraData");
+        Player::register_hooks(app.entity_manager_mut());
+    }
+}
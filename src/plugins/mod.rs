@@ -0,0 +1,12 @@
+// src/plugins/mod.rs
+// Description:
+// This module contains the plugins the engine's features are split into. Every game wants the CorePlugin,
+// the others are opt-in: a game without a top-down map simply doesn't add the TopDownDebugPlugin.
+// Plugins that need the keyboard are only built with the `sdl` feature, the render plugins draw into whatever
+// renderer the app was given.
+
+pub mod core;             // Module for movement, collision, hierarchy, players and the world map
+#[cfg(feature = "sdl")]
+pub mod player_control;   // Module for the keyboard-controlled player
+pub mod raycast_render;   // Module for the first person raycast view
+pub mod top_down_debug;   // Module for the top-down view of the map and entities
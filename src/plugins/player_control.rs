@@ -0,0 +1,16 @@
+// src/plugins/player_control.rs
+// Description: The PlayerControlPlugin lets the player prefab be steered with the keyboard.
+// The InputHandler has to be inserted as a non-send resource before the game starts.
+
+use crate::app::{App, Plugin};
+use crate::ecs::system::player_controller::PlayerController;
+use crate::plugins::core::CorePlugin;
+
+pub struct PlayerControlPlugin;
+
+impl Plugin for PlayerControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(CorePlugin)
+            .register_prefab_system("PlayerController", || PlayerController);
+    }
+}
@@ -0,0 +1,20 @@
+// src/plugins/raycast_render.rs
+// Description: The RaycastRenderPlugin draws the world in first person from every entity with a camera.
+// A renderer has to be inserted with `App::insert_renderer` before the game starts. Walls are textured from the
+// TextureAtlas resource, the generated default textures are used unless the app inserted its own first.
+
+use crate::app::{App, Plugin};
+use crate::ecs::system::camera_system::Camera_System;
+use crate::plugins::core::CorePlugin;
+use crate::render::texture::TextureAtlas;
+
+pub struct RaycastRenderPlugin;
+
+impl Plugin for RaycastRenderPlugin {
+    fn build(&self, app: &mut App) {
+        if !app.entity_manager_mut().has_resource::<TextureAtlas>() {
+            app.insert_resource(TextureAtlas::with_defaults());
+        }
+        app.add_plugin(CorePlugin).add_system(Camera_System::new());
+    }
+}
@@ -0,0 +1,15 @@
+// src/plugins/top_down_debug.rs
+// Description: The TopDownDebugPlugin draws the map and every entity with RenderData from above,
+// which is handy for checking collisions and positions.
+
+use crate::app::{App, Plugin};
+use crate::ecs::system::{render_system::RenderSystem, world_system::WorldSystem};
+use crate::plugins::core::CorePlugin;
+
+pub struct TopDownDebugPlugin;
+
+impl Plugin for TopDownDebugPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugin(CorePlugin).add_system(RenderSystem).add_system(WorldSystem);
+    }
+}
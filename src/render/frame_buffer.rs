@@ -0,0 +1,294 @@
+// src/render/frame_buffer.rs
+// Description:
+// This module contains the FrameBuffer, a renderer that draws into an RGBA pixel array in memory.
+// It draws the same pixels SDL does for rects and lines, so a frame rendered here looks like the window would.
+
+use crate::render::{Renderer, CLEAR_COLOR};
+use std::any::Any;
+use std::cell::{Cell, RefCell};
+
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pixels: RefCell<Vec<u8>>, // 4 bytes a pixel, row by row from the top left
+    frames: Cell<u64>,        // how often present was called
+}
+
+impl FrameBuffer {
+    pub fn new(width: u32, height: u32) -> Self {
+        let frame_buffer = FrameBuffer {
+            width,
+            height,
+            pixels: RefCell::new(vec![0; width as usize * height as usize * 4]),
+            frames: Cell::new(0),
+        };
+        frame_buffer.clear();
+        frame_buffer
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// A copy of the RGBA bytes of the frame, row by row from the top left
+    pub fn pixels(&self) -> Vec<u8> {
+        self.pixels.borrow().clone()
+    }
+
+    pub fn frames_presented(&self) -> u64 {
+        self.frames.get()
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = self.index(x, y);
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels.borrow()[index..index + 4]);
+        Some(pixel)
+    }
+
+    /// Sets one pixel, anything outside the frame is ignored
+    pub fn set_pixel(&self, x: i32, y: i32, r: u8, g: u8, b: u8) {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return;
+        }
+        let index = self.index(x as u32, y as u32);
+        self.pixels.borrow_mut()[index..index + 4].copy_from_slice(&[r, g, b, 255]);
+    }
+
+    // Byte offset of a pixel inside the frame, worked out in usize so big frames don't overflow
+    fn index(&self, x: u32, y: u32) -> usize {
+        (y as usize * self.width as usize + x as usize) * 4
+    }
+
+    // Cuts the line down to the part inside the frame, None if it misses the frame. Cohen-Sutherland like SDL,
+    // so a clipped line still draws the pixels SDL would.
+    fn clip_line(&self, mut x1: i64, mut y1: i64, mut x2: i64, mut y2: i64) -> Option<(i64, i64, i64, i64)> {
+        const LEFT: u8 = 1;
+        const RIGHT: u8 = 2;
+        const TOP: u8 = 4;
+        const BOTTOM: u8 = 8;
+        let (right, bottom) = (self.width as i64 - 1, self.height as i64 - 1);
+        let outcode = |x: i64, y: i64| {
+            let mut code = 0;
+            if x < 0 {
+                code |= LEFT;
+            } else if x > right {
+                code |= RIGHT;
+            }
+            if y < 0 {
+                code |= TOP;
+            } else if y > bottom {
+                code |= BOTTOM;
+            }
+            code
+        };
+
+        let (mut code1, mut code2) = (outcode(x1, y1), outcode(x2, y2));
+        loop {
+            if code1 | code2 == 0 {
+                return Some((x1, y1, x2, y2));
+            }
+            if code1 & code2 != 0 {
+                return None;
+            }
+            // Move the end point that is outside onto the edge it crosses
+            let code = if code1 != 0 { code1 } else { code2 };
+            let (x, y) = if code & TOP != 0 {
+                (x1 + (x2 - x1) * -y1 / (y2 - y1), 0)
+            } else if code & BOTTOM != 0 {
+                (x1 + (x2 - x1) * (bottom - y1) / (y2 - y1), bottom)
+            } else if code & LEFT != 0 {
+                (0, y1 + (y2 - y1) * -x1 / (x2 - x1))
+            } else {
+                (right, y1 + (y2 - y1) * (right - x1) / (x2 - x1))
+            };
+            if code == code1 {
+                (x1, y1) = (x, y);
+                code1 = outcode(x1, y1);
+            } else {
+                (x2, y2) = (x, y);
+                code2 = outcode(x2, y2);
+            }
+        }
+    }
+}
+
+impl Renderer for FrameBuffer {
+    fn clear(&self) {
+        let (r, g, b) = CLEAR_COLOR;
+        for pixel in self.pixels.borrow_mut().chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[r, g, b, 255]);
+        }
+    }
+
+    fn present(&self) {
+        self.frames.set(self.frames.get() + 1);
+    }
+
+    fn draw_filled_rect(&self, x: i32, y: i32, size_x: u32, size_y: u32, r: u8, g: u8, b: u8) {
+        // Clip to the frame first so huge rects don't loop over pixels that aren't there
+        let start_x = x.max(0);
+        let start_y = y.max(0);
+        let end_x = (x as i64 + size_x as i64).min(self.width as i64) as i32;
+        let end_y = (y as i64 + size_y as i64).min(self.height as i64) as i32;
+        if start_x >= end_x || start_y >= end_y {
+            return;
+        }
+
+        let mut pixels = self.pixels.borrow_mut();
+        for pixel_y in start_y as u32..end_y as u32 {
+            let row_start = self.index(start_x as u32, pixel_y);
+            let row_end = self.index(end_x as u32, pixel_y);
+            for pixel in pixels[row_start..row_end].chunks_exact_mut(4) {
+                pixel.copy_from_slice(&[r, g, b, 255]);
+            }
+        }
+    }
+
+    // Bresenham's line, both end points included like SDL. Clipped to the frame first so a line reaching far
+    // outside doesn't walk billions of points, in i64 so the differences can't overflow
+    fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, r: u8, g: u8, b: u8) {
+        let Some((x1, y1, x2, y2)) = self.clip_line(x1 as i64, y1 as i64, x2 as i64, y2 as i64) else {
+            return;
+        };
+        let (mut x, mut y) = (x1, y1);
+        let delta_x = (x2 - x1).abs();
+        let delta_y = -(y2 - y1).abs();
+        let step_x = if x1 < x2 { 1 } else { -1 };
+        let step_y = if y1 < y2 { 1 } else { -1 };
+        let mut error = delta_x + delta_y;
+        loop {
+            // Inside the frame after clipping, so these fit an i32
+            self.set_pixel(x as i32, y as i32, r, g, b);
+            if x == x2 && y == y2 {
+                break;
+            }
+            let doubled = 2 * error;
+            if doubled >= delta_y {
+                error += delta_y;
+                x += step_x;
+            }
+            if doubled <= delta_x {
+                error += delta_x;
+                y += step_y;
+            }
+        }
+    }
+
+    fn draw_rect(&self, x: i32, y: i32, width: u32, height: u32, r: u8, g: u8, b: u8) {
+        if width == 0 || height == 0 {
+            return;
+        }
+        // Worked out in i64 so huge rects don't overflow, an edge past i32::MAX is off the frame anyway
+        let right = (x as i64 + width as i64 - 1).min(i32::MAX as i64) as i32;
+        let bottom = (y as i64 + height as i64 - 1).min(i32::MAX as i64) as i32;
+        self.draw_filled_rect(x, y, width, 1, r, g, b);
+        self.draw_filled_rect(x, bottom, width, 1, r, g, b);
+        self.draw_filled_rect(x, y, 1, height, r, g, b);
+        self.draw_filled_rect(right, y, 1, height, r, g, b);
+    }
+
+    fn draw_image(&self, x: i32, y: i32, width: u32, height: u32, pixels: &[u8]) {
+        if pixels.len() / 4 < width as usize * height as usize {
+            return;
+        }
+        let mut frame = self.pixels.borrow_mut();
+        for row in 0..height {
+            let frame_y = y as i64 + row as i64;
+            if frame_y < 0 || frame_y >= self.height as i64 {
+                continue;
+            }
+            for column in 0..width {
+                let frame_x = x as i64 + column as i64;
+                if frame_x < 0 || frame_x >= self.width as i64 {
+                    continue;
+                }
+                let source = (row as usize * width as usize + column as usize) * 4;
+                let alpha = pixels[source + 3] as u32;
+                if alpha == 0 {
+                    continue;
+                }
+                let target = self.index(frame_x as u32, frame_y as u32);
+                for channel in 0..3 {
+                    let blended = pixels[source + channel] as u32 * alpha + frame[target + channel] as u32 * (255 - alpha);
+                    frame[target + channel] = (blended / 255) as u8;
+                }
+                frame[target + 3] = 255;
+            }
+        }
+    }
+
+    fn get_window_size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn huge_rects_are_clipped_to_the_frame() {
+        let frame = FrameBuffer::new(4, 4);
+        frame.draw_rect(1, 1, u32::MAX, u32::MAX, 255, 0, 0);
+        frame.draw_filled_rect(-10, 3, u32::MAX, 5, 0, 255, 0);
+
+        assert_eq!(frame.get_pixel(1, 1), Some([255, 0, 0, 255]));
+        assert_eq!(frame.get_pixel(3, 1), Some([255, 0, 0, 255]));
+        assert_eq!(frame.get_pixel(2, 2), frame.get_pixel(0, 0));
+        assert_eq!(frame.get_pixel(0, 3), Some([0, 255, 0, 255]));
+        assert_eq!(frame.get_pixel(3, 3), Some([0, 255, 0, 255]));
+    }
+
+    #[test]
+    fn lines_reaching_far_outside_are_clipped() {
+        let frame = FrameBuffer::new(4, 4);
+        let background = frame.get_pixel(0, 0);
+        frame.draw_line(i32::MIN, i32::MIN, i32::MAX, i32::MAX, 255, 0, 0);
+        for i in 0..4 {
+            assert_eq!(frame.get_pixel(i, i), Some([255, 0, 0, 255]));
+        }
+        assert_eq!(frame.get_pixel(1, 0), background);
+
+        frame.draw_line(-1_000_000, 2, 1_000_000, 2, 0, 255, 0);
+        for x in 0..4 {
+            assert_eq!(frame.get_pixel(x, 2), Some([0, 255, 0, 255]));
+        }
+
+        // Entirely outside, nothing is drawn
+        frame.draw_line(i32::MIN, -1, i32::MAX, -1, 0, 0, 255);
+        frame.draw_line(5, i32::MIN, 5, i32::MAX, 0, 0, 255);
+        assert!(frame.pixels().chunks_exact(4).all(|pixel| pixel != [0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn lines_inside_the_frame_keep_both_end_points() {
+        let frame = FrameBuffer::new(8, 8);
+        frame.draw_line(6, 1, 1, 3, 255, 255, 255);
+        assert_eq!(frame.get_pixel(6, 1), Some([255, 255, 255, 255]));
+        assert_eq!(frame.get_pixel(1, 3), Some([255, 255, 255, 255]));
+    }
+
+    #[test]
+    fn images_too_small_for_their_size_are_ignored() {
+        let frame = FrameBuffer::new(4, 4);
+        let before = frame.pixels();
+        frame.draw_image(0, 0, u32::MAX, u32::MAX, &[255; 64]);
+        frame.draw_image(0, 0, 4, 4, &[255; 63]);
+        assert_eq!(frame.pixels(), before);
+
+        frame.draw_image(i32::MAX, i32::MAX, 4, 4, &[255; 64]);
+        assert_eq!(frame.pixels(), before);
+    }
+}
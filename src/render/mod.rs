@@ -0,0 +1,50 @@
+// src/render/mod.rs
+// Description:
+// This module contains the Renderer trait, everything the systems draw goes through it. The SDLWindowManager draws
+// into a window, the FrameBuffer draws into memory so rendering works without a display (tests, CI).
+// The renderer is stored as a `Box<dyn Renderer>` non-send resource, see `App::insert_renderer`.
+
+pub mod frame_buffer; // Module for the in-memory RGBA renderer
+pub mod texture;      // Module for wall textures and the texture atlas
+
+use crate::error::EngineError;
+use std::any::Any;
+
+/// The colour every frame starts with
+pub const CLEAR_COLOR: (u8, u8, u8) = (222, 165, 164);
+
+pub trait Renderer {
+    /// Fills the whole target with CLEAR_COLOR
+    fn clear(&self);
+
+    /// Shows what was drawn since the last clear
+    fn present(&self);
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_filled_rect(&self, x: i32, y: i32, size_x: u32, size_y: u32, r: u8, g: u8, b: u8);
+
+    #[allow(clippy::too_many_arguments)]
+    fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, r: u8, g: u8, b: u8);
+
+    /// Draws a one pixel outline
+    #[allow(clippy::too_many_arguments)]
+    fn draw_rect(&self, x: i32, y: i32, width: u32, height: u32, r: u8, g: u8, b: u8);
+
+    /// Draws an RGBA image, row by row from the top left, with its top left corner at x, y.
+    /// Pixels are blended over what is already drawn by their alpha, so an alpha of 0 leaves the pixel alone.
+    fn draw_image(&self, x: i32, y: i32, width: u32, height: u32, pixels: &[u8]);
+
+    fn get_window_size(&self) -> (u32, u32);
+
+    /// The window title, renderers without a window have none
+    fn title(&self) -> String {
+        String::new()
+    }
+
+    fn set_title(&self, _title: &str) -> Result<(), EngineError> {
+        Ok(())
+    }
+
+    // Lets tests and tools get at the concrete renderer, e.g. to read back a FrameBuffer
+    fn as_any(&self) -> &dyn Any;
+}
@@ -0,0 +1,293 @@
+// src/render/texture.rs
+// Description:
+// This module contains the textures the raycaster draws walls with. A TextureAtlas is a resource holding square
+// textures, a map tile says which one its walls use by index. Atlases are loaded from a PNG with the textures side
+// by side, without one the atlas falls back to a few textures generated in code (brick, stone, wood and metal).
+
+use crate::error::EngineError;
+use std::fs::File;
+use std::path::Path;
+
+/// Size of the generated default textures
+pub const DEFAULT_TEXTURE_SIZE: u32 = 64;
+
+/// An RGBA image, row by row from the top left
+#[derive(Clone)]
+pub struct Texture {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl Texture {
+    /// `pixels` has to hold 4 bytes for every pixel
+    pub fn new(width: u32, height: u32, pixels: Vec<u8>) -> Result<Self, EngineError> {
+        // In u64 so the size of a huge texture doesn't wrap around and pass the check
+        let size = width as u64 * height as u64 * 4;
+        if width == 0 || height == 0 || pixels.len() as u64 != size {
+            return Err(EngineError::Load(format!(
+                "A {}x{} texture needs {} bytes, got {}",
+                width,
+                height,
+                size,
+                pixels.len()
+            )));
+        }
+        Ok(Texture { width, height, pixels })
+    }
+
+    /// Fills a texture by calling `color` for every pixel. Panics if the width or height is 0, which `new` rejects
+    pub fn generate(width: u32, height: u32, color: impl Fn(u32, u32) -> [u8; 4]) -> Self {
+        assert!(width > 0 && height > 0, "A texture can't be {}x{}", width, height);
+        let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+        for y in 0..height {
+            for x in 0..width {
+                pixels.extend_from_slice(&color(x, y));
+            }
+        }
+        Texture { width, height, pixels }
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    /// The RGBA colour at the pixel, coordinates wrap around
+    pub fn get(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = ((y % self.height) as usize * self.width as usize + (x % self.width) as usize) * 4;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[index..index + 4]);
+        pixel
+    }
+
+    /// Samples with texture coordinates between 0 and 1
+    pub fn sample(&self, u: f32, v: f32) -> [u8; 4] {
+        let x = ((u * self.width as f32) as u32).min(self.width - 1);
+        let y = ((v * self.height as f32) as u32).min(self.height - 1);
+        self.get(x, y)
+    }
+}
+
+pub struct TextureAtlas {
+    textures: Vec<Texture>,
+}
+
+impl Default for TextureAtlas {
+    fn default() -> Self {
+        Self::with_defaults()
+    }
+}
+
+impl TextureAtlas {
+    /// An atlas with no textures, add some before drawing with it
+    pub fn new() -> Self {
+        TextureAtlas { textures: Vec::new() }
+    }
+
+    /// The generated textures: 0 brick, 1 stone, 2 wood, 3 metal for walls and floors, then the sprites
+    /// 4 orb and 5 barrel which are see-through around their edges
+    pub fn with_defaults() -> Self {
+        let size = DEFAULT_TEXTURE_SIZE;
+        TextureAtlas { textures: vec![brick(size), stone(size), wood(size), metal(size), orb(size), barrel(size)] }
+    }
+
+    /// Splits an RGBA image into square textures of `tile_size`, left to right and then top to bottom
+    pub fn from_image(image: &Texture, tile_size: u32) -> Result<Self, EngineError> {
+        if tile_size == 0 || !image.width.is_multiple_of(tile_size) || !image.height.is_multiple_of(tile_size) {
+            return Err(EngineError::Load(format!(
+                "A {}x{} image can't be split into {}x{} textures",
+                image.width, image.height, tile_size, tile_size
+            )));
+        }
+        let mut atlas = TextureAtlas::new();
+        for tile_y in 0..image.height / tile_size {
+            for tile_x in 0..image.width / tile_size {
+                atlas.add(Texture::generate(tile_size, tile_size, |x, y| {
+                    image.get(tile_x * tile_size + x, tile_y * tile_size + y)
+                }));
+            }
+        }
+        Ok(atlas)
+    }
+
+    /// Loads a PNG atlas, see `from_image`
+    pub fn load_file(path: impl AsRef<Path>, tile_size: u32) -> Result<Self, EngineError> {
+        Self::from_image(&load_png(path.as_ref())?, tile_size)
+    }
+
+    /// Adds a texture and returns its index
+    pub fn add(&mut self, texture: Texture) -> usize {
+        self.textures.push(texture);
+        self.textures.len() - 1
+    }
+
+    /// Indices past the end wrap around, so a map using more textures than the atlas has still draws
+    pub fn get(&self, index: usize) -> Option<&Texture> {
+        if self.textures.is_empty() {
+            return None;
+        }
+        self.textures.get(index % self.textures.len())
+    }
+
+    pub fn len(&self) -> usize {
+        self.textures.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.textures.is_empty()
+    }
+}
+
+/// Reads a PNG file into an RGBA texture, other colour types are converted
+pub fn load_png(path: &Path) -> Result<Texture, EngineError> {
+    let error = |error: &dyn std::fmt::Display| EngineError::Load(format!("Failed to read {}: {}", path.display(), error));
+    let file = File::open(path).map_err(|e| error(&e))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::normalize_to_color8());
+    let mut reader = decoder.read_info().map_err(|e| error(&e))?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| error(&e))?;
+    buffer.truncate(info.buffer_size());
+
+    let pixels = match info.color_type {
+        png::ColorType::Rgba => buffer,
+        png::ColorType::Rgb => buffer.chunks_exact(3).flat_map(|rgb| [rgb[0], rgb[1], rgb[2], 255]).collect(),
+        png::ColorType::GrayscaleAlpha => buffer.chunks_exact(2).flat_map(|ga| [ga[0], ga[0], ga[0], ga[1]]).collect(),
+        png::ColorType::Grayscale => buffer.iter().flat_map(|&g| [g, g, g, 255]).collect(),
+        png::ColorType::Indexed => return Err(error(&"indexed colours weren't expanded")),
+    };
+    Texture::new(info.width, info.height, pixels)
+}
+
+// Cheap repeatable noise for the generated textures, 0 to 255
+fn noise(x: u32, y: u32, seed: u32) -> u32 {
+    let mut hash = x.wrapping_mul(374_761_393) ^ y.wrapping_mul(668_265_263) ^ seed.wrapping_mul(2_246_822_519);
+    hash = (hash ^ (hash >> 13)).wrapping_mul(1_274_126_177);
+    (hash ^ (hash >> 16)) & 255
+}
+
+// Scales a colour by a percentage, for the speckles in the generated textures
+fn tint(color: [u8; 3], percent: u32) -> [u8; 4] {
+    let scale = |channel: u8| (channel as u32 * percent / 100).min(255) as u8;
+    [scale(color[0]), scale(color[1]), scale(color[2]), 255]
+}
+
+// Red bricks in rows offset by half a brick, with grey mortar
+fn brick(size: u32) -> Texture {
+    let brick_height = size / 4;
+    let brick_width = size / 2;
+    Texture::generate(size, size, |x, y| {
+        let row = y / brick_height;
+        let offset = if row.is_multiple_of(2) { 0 } else { brick_width / 2 };
+        let mortar = y % brick_height == 0 || (x + offset) % brick_width == 0;
+        if mortar {
+            tint([170, 165, 155], 90 + noise(x, y, 1) % 15)
+        } else {
+            tint([165, 65, 45], 85 + noise(x, y, 2) % 25)
+        }
+    })
+}
+
+// Big grey blocks with darker joints
+fn stone(size: u32) -> Texture {
+    let block = size / 2;
+    Texture::generate(size, size, |x, y| {
+        let offset = if (y / block).is_multiple_of(2) { 0 } else { block / 2 };
+        if y % block == 0 || (x + offset) % block == 0 {
+            tint([80, 80, 85], 100)
+        } else {
+            tint([135, 135, 140], 80 + noise(x / 2, y / 2, 3) % 35)
+        }
+    })
+}
+
+// Vertical brown planks with a grain
+fn wood(size: u32) -> Texture {
+    let plank = size / 4;
+    Texture::generate(size, size, |x, y| {
+        if x % plank == 0 {
+            tint([70, 45, 25], 100)
+        } else {
+            let grain = (y + noise(x / 3, 0, 4) % 16).is_multiple_of(8);
+            tint([145, 100, 60], if grain { 80 } else { 95 + noise(x, y / 4, 5) % 10 })
+        }
+    })
+}
+
+// Blue-grey panels with rivets in the corners
+fn metal(size: u32) -> Texture {
+    let panel = size / 2;
+    Texture::generate(size, size, |x, y| {
+        let (panel_x, panel_y) = (x % panel, y % panel);
+        let rivet = [3, panel - 4].contains(&panel_x) && [3, panel - 4].contains(&panel_y);
+        if panel_x == 0 || panel_y == 0 {
+            tint([50, 60, 70], 100)
+        } else if rivet {
+            tint([200, 210, 220], 100)
+        } else {
+            tint([100, 115, 130], 92 + noise(x, y, 6) % 10)
+        }
+    })
+}
+
+// A glowing green ball, lighter towards the top left
+fn orb(size: u32) -> Texture {
+    let radius = size as f32 / 2.0;
+    Texture::generate(size, size, |x, y| {
+        let (dx, dy) = (x as f32 + 0.5 - radius, y as f32 + 0.5 - radius);
+        let distance = (dx * dx + dy * dy).sqrt() / radius;
+        if distance > 1.0 {
+            return [0, 0, 0, 0];
+        }
+        let highlight = ((dx + dy) / (2.0 * radius) + 0.5).clamp(0.0, 1.0);
+        let light = (1.0 - highlight) * 0.6 + (1.0 - distance) * 0.4;
+        [(60.0 + 150.0 * light) as u8, (150.0 + 105.0 * light) as u8, (70.0 + 120.0 * light) as u8, 255]
+    })
+}
+
+// A wooden barrel with two metal bands, narrower at the top and bottom
+fn barrel(size: u32) -> Texture {
+    let half = size as f32 / 2.0;
+    Texture::generate(size, size, |x, y| {
+        let height = (y as f32 + 0.5) / size as f32;
+        let bulge = 1.0 - (height - 0.5).powi(2) * 0.8; // 0.8 at the ends, 1 in the middle
+        let dx = (x as f32 + 0.5 - half) / (half * 0.85 * bulge);
+        if dx.abs() > 1.0 || y < size / 8 {
+            return [0, 0, 0, 0];
+        }
+        let rounding = 100 - (dx.abs() * 35.0) as u32; // darker towards the sides
+        let band = [size / 4, size / 4 + 1, size * 3 / 4, size * 3 / 4 + 1].contains(&y);
+        if band {
+            tint([120, 120, 125], rounding)
+        } else if x.is_multiple_of(size / 8) {
+            tint([80, 50, 25], rounding)
+        } else {
+            tint([140, 90, 45], rounding * (90 + noise(x, y / 6, 7) % 10) / 100)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_checks_the_size_without_overflowing() {
+        assert!(Texture::new(2, 1, vec![0; 8]).is_ok());
+        assert!(Texture::new(2, 1, vec![0; 7]).is_err());
+        assert!(Texture::new(0, 1, Vec::new()).is_err());
+        // 65536 * 65536 * 4 wraps to 0 in u32
+        let error = Texture::new(65536, 65536, Vec::new()).err().expect("The size should have been rejected");
+        assert!(error.to_string().contains("needs 17179869184 bytes"), "{}", error);
+    }
+
+    #[test]
+    #[should_panic(expected = "A texture can't be 0x4")]
+    fn generate_rejects_empty_textures() {
+        Texture::generate(0, 4, |_, _| [0; 4]);
+    }
+}
@@ -1,7 +1,10 @@
-use sdl2::pixels::Color;
+use sdl2::pixels::{Color, PixelFormatEnum};
 use sdl2::rect::{Point, Rect};
-use sdl2::render::Canvas;
+use sdl2::render::{BlendMode, Canvas, Texture};
 use sdl2::video::Window;
+use crate::error::EngineError;
+use crate::render::{Renderer, CLEAR_COLOR};
+use std::any::Any;
 use std::cell::RefCell;
 use std::rc::Rc;
 
@@ -9,49 +12,105 @@ pub struct SDLWindowManager {
     canvas: Rc<RefCell<Canvas<Window>>>,
     window_width: u32,
     window_height: u32,
+    // Streaming texture draw_image uploads into, with its size. Only recreated when an image doesn't fit.
+    image_texture: RefCell<Option<(Texture, u32, u32)>>,
 }
 
 impl SDLWindowManager {
     pub fn new(canvas: Rc<RefCell<Canvas<Window>>>, width: u32,height: u32) -> Self {
-        SDLWindowManager { canvas, window_width: width, window_height: height }
+        SDLWindowManager { canvas, window_width: width, window_height: height, image_texture: RefCell::new(None) }
     }
 
     pub fn builder() -> SDLWindowManagerBuilder {
         SDLWindowManagerBuilder::default()
     }
+}
 
-    pub fn clear(&self) {
+impl Renderer for SDLWindowManager {
+    fn clear(&self) {
         let mut canvas = self.canvas.borrow_mut();
-        canvas.set_draw_color(Color::RGB(222, 165, 164));
+        let (r, g, b) = CLEAR_COLOR;
+        canvas.set_draw_color(Color::RGB(r, g, b));
         canvas.clear();
     }
 
-    pub fn present(&self) {
+    fn present(&self) {
         self.canvas.borrow_mut().present();
     }
 
-    pub fn draw_filled_rect(&self, x: i32, y: i32, size_x: u32, size_y: u32, r: u8, g: u8, b: u8) {
+    fn draw_filled_rect(&self, x: i32, y: i32, size_x: u32, size_y: u32, r: u8, g: u8, b: u8) {
         let mut canvas = self.canvas.borrow_mut();
         canvas.set_draw_color(Color::RGB(r, g, b));
         let _ = canvas.fill_rect(Rect::new(x, y, size_x, size_y));
     }
     
-    pub fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, r: u8, g: u8, b: u8) {
+    fn draw_line(&self, x1: i32, y1: i32, x2: i32, y2: i32, r: u8, g: u8, b: u8) {
         let mut canvas = self.canvas.borrow_mut();
         canvas.set_draw_color(Color::RGB(r, g, b));
         let _ = canvas.draw_line(Point::new(x1, y1), Point::new(x2, y2));
     }
 
-    pub fn draw_rect(&self, x: i32, y: i32, width: u32, height: u32, r: u8, g: u8, b: u8) {
+    fn draw_rect(&self, x: i32, y: i32, width: u32, height: u32, r: u8, g: u8, b: u8) {
         let mut canvas = self.canvas.borrow_mut();
         canvas.set_draw_color(Color::RGB(r, g, b));
         let _ = canvas.draw_rect(Rect::new(x, y, width, height));
     }
 
-    pub fn get_window_size(&self) -> (u32, u32) {
+    // Uploads the image into the top left of one streaming texture kept between calls and copies that part
+    // to the window. The texture grows when an image doesn't fit, so after the first frames it is only updated.
+    fn draw_image(&self, x: i32, y: i32, width: u32, height: u32, pixels: &[u8]) {
+        if width == 0 || height == 0 || pixels.len() < (width * height * 4) as usize {
+            return;
+        }
+        let mut canvas = self.canvas.borrow_mut();
+        let mut image_texture = self.image_texture.borrow_mut();
+
+        let fits = image_texture
+            .as_ref()
+            .is_some_and(|(_, texture_width, texture_height)| width <= *texture_width && height <= *texture_height);
+        if !fits {
+            let (texture_width, texture_height) = match image_texture.take() {
+                Some((texture, texture_width, texture_height)) => {
+                    // Safety: the canvas the texture was created from is still alive, it's borrowed right here
+                    unsafe { texture.destroy() };
+                    (texture_width.max(width), texture_height.max(height))
+                }
+                None => (width, height),
+            };
+            let Ok(mut texture) = canvas.create_texture_streaming(PixelFormatEnum::RGBA32, texture_width, texture_height)
+            else {
+                return;
+            };
+            texture.set_blend_mode(BlendMode::Blend);
+            *image_texture = Some((texture, texture_width, texture_height));
+        }
+
+        let Some((texture, _, _)) = image_texture.as_mut() else {
+            return;
+        };
+        let area = Rect::new(0, 0, width, height);
+        if texture.update(area, pixels, (width * 4) as usize).is_ok() {
+            let _ = canvas.copy(texture, area, Rect::new(x, y, width, height));
+        }
+    }
+
+    fn get_window_size(&self) -> (u32, u32) {
         let canvas = self.canvas.borrow();
-        let size = canvas.output_size().unwrap();
-        (size.0, size.1)
+        // Falls back to the size the window was created with if SDL can't tell
+        canvas.output_size().unwrap_or((self.window_width, self.window_height))
+    }
+
+    fn title(&self) -> String {
+        self.canvas.borrow().window().title().to_string()
+    }
+
+    fn set_title(&self, title: &str) -> Result<(), EngineError> {
+        let mut canvas = self.canvas.borrow_mut();
+        canvas.window_mut().set_title(title).map_err(|error| EngineError::Sdl(error.to_string()))
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
     }
 }
 
@@ -77,17 +136,20 @@ impl SDLWindowManagerBuilder {
         self
     }
 
-    pub fn build(self) -> SDLWindowManager {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
+    pub fn build(self) -> Result<SDLWindowManager, EngineError> {
+        let sdl_context = sdl2::init().map_err(EngineError::Sdl)?;
+        let video_subsystem = sdl_context.video().map_err(EngineError::Sdl)?;
         let window = video_subsystem
             .window(&self.title, self.width, self.height)
             .position_centered()
             .build()
-            .unwrap();
-        let canvas = Rc::new(RefCell::new(window.into_canvas().build().unwrap()));
+            .map_err(|error| EngineError::Sdl(error.to_string()))?;
+        let canvas = window
+            .into_canvas()
+            .build()
+            .map_err(|error| EngineError::Sdl(error.to_string()))?;
 
-        SDLWindowManager::new(canvas, self.width, self.height)
+        Ok(SDLWindowManager::new(Rc::new(RefCell::new(canvas)), self.width, self.height))
     }
 }
 
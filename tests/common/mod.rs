@@ -0,0 +1,98 @@
+// tests/common/mod.rs
+// Description:
+// Golden image checks shared by the rendering tests. A frame rendered into a FrameBuffer is compared with a PNG
+// checked in under tests/golden. Small differences are allowed so a rounding change on another platform doesn't
+// fail the test, bigger ones fail it and write the actual frame and a diff image next to the test binaries.
+// Run with UPDATE_GOLDEN=1 to write the current frames as the new goldens after an intended change.
+
+use game::render::frame_buffer::FrameBuffer;
+use std::fs::File;
+use std::io::BufWriter;
+use std::path::{Path, PathBuf};
+
+// How far a channel may be off before the pixel counts as different
+const CHANNEL_TOLERANCE: u8 = 2;
+// How many pixels may be different, as a fraction of the frame
+const MAX_DIFFERENT_PIXELS: f32 = 0.01;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden").join(format!("{}.png", name))
+}
+
+fn read_png(path: &Path) -> Result<(u32, u32, Vec<u8>), String> {
+    let file = File::open(path).map_err(|error| format!("Failed to open {}: {}", path.display(), error))?;
+    let mut decoder = png::Decoder::new(file);
+    decoder.set_transformations(png::Transformations::EXPAND);
+    let mut reader = decoder.read_info().map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).map_err(|error| format!("Failed to read {}: {}", path.display(), error))?;
+    if info.color_type != png::ColorType::Rgba || info.bit_depth != png::BitDepth::Eight {
+        return Err(format!("{} is not an 8 bit RGBA PNG", path.display()));
+    }
+    pixels.truncate(info.buffer_size());
+    Ok((info.width, info.height, pixels))
+}
+
+fn write_png(path: &Path, width: u32, height: u32, pixels: &[u8]) {
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory).expect("Failed to create the image directory");
+    }
+    let file = File::create(path).unwrap_or_else(|error| panic!("Failed to create {}: {}", path.display(), error));
+    let mut encoder = png::Encoder::new(BufWriter::new(file), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().expect("Failed to write the PNG header");
+    writer.write_image_data(pixels).expect("Failed to write the PNG data");
+}
+
+// Different pixels in red over a faded copy of the golden, so it's easy to see where they are
+fn diff_image(expected: &[u8], actual: &[u8]) -> (Vec<u8>, usize) {
+    let mut different = 0;
+    let mut diff = Vec::with_capacity(expected.len());
+    for (expected, actual) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let differs = expected.iter().zip(actual).any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE);
+        if differs {
+            different += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            let grey = ((expected[0] as u32 + expected[1] as u32 + expected[2] as u32) / 3 / 3 + 170) as u8;
+            diff.extend_from_slice(&[grey, grey, grey, 255]);
+        }
+    }
+    (diff, different)
+}
+
+/// Panics if the frame doesn't match tests/golden/<name>.png
+pub fn assert_matches_golden(name: &str, frame: &FrameBuffer) {
+    let path = golden_path(name);
+    let (width, height, actual) = (frame.width(), frame.height(), frame.pixels());
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_png(&path, width, height, &actual);
+        return;
+    }
+
+    let (golden_width, golden_height, expected) = read_png(&path)
+        .unwrap_or_else(|error| panic!("{}, run with UPDATE_GOLDEN=1 to create it", error));
+    let output = Path::new(env!("CARGO_TARGET_TMPDIR")).join("golden");
+    let actual_path = output.join(format!("{}.actual.png", name));
+    if (golden_width, golden_height) != (width, height) {
+        write_png(&actual_path, width, height, &actual);
+        panic!(
+            "{} is {}x{} but the frame is {}x{}, the frame was written to {}",
+            name, golden_width, golden_height, width, height, actual_path.display()
+        );
+    }
+
+    let (diff, different) = diff_image(&expected, &actual);
+    let allowed = ((width * height) as f32 * MAX_DIFFERENT_PIXELS) as usize;
+    if different > allowed {
+        let diff_path = output.join(format!("{}.diff.png", name));
+        write_png(&actual_path, width, height, &actual);
+        write_png(&diff_path, width, height, &diff);
+        panic!(
+            "{}: {} pixels differ from the golden, at most {} may. The frame was written to {} and the diff to {}",
+            name, different, allowed, actual_path.display(), diff_path.display()
+        );
+    }
+}
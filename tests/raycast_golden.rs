@@ -0,0 +1,100 @@
+// tests/raycast_golden.rs
+// Description:
+// Renders the first person view from fixed positions in the test map and compares it with the goldens in
+// tests/golden. Runs headless, `cargo test --no-default-features` works without SDL.
+
+mod common;
+
+use game::ecs::component::camera_data::CameraData;
+use game::ecs::component::sprite::Sprite;
+use game::ecs::component::transform_data::{GlobalTransform, Position};
+use game::ecs::component::world_data::{Surface, WorldData};
+use game::ecs::system::camera_system::Camera_System;
+use game::render::frame_buffer::FrameBuffer;
+use game::render::texture::TextureAtlas;
+use game::render::Renderer;
+use std::f32::consts::PI;
+
+const FRAME_SIZE: u32 = 160;
+const TILES: u32 = 20; // the test map, 8 pixel cells with the frame size above
+
+fn render(position: Position) -> FrameBuffer {
+    render_world(&WorldData::new(TILES, FRAME_SIZE, FRAME_SIZE), position)
+}
+
+fn render_world(world: &WorldData, position: Position) -> FrameBuffer {
+    render_with_sprites(world, position, &[])
+}
+
+// Sprites are given as x, y, texture and scale
+fn render_with_sprites(world: &WorldData, position: Position, sprites: &[(f32, f32, u32, f32)]) -> FrameBuffer {
+    let frame = FrameBuffer::new(FRAME_SIZE, FRAME_SIZE);
+    let player = GlobalTransform { position };
+    let (camera, textures) = (CameraData::new(), TextureAtlas::with_defaults());
+    let sprites: Vec<(GlobalTransform, Sprite)> = sprites
+        .iter()
+        .map(|&(x, y, texture, scale)| (GlobalTransform { position: Position::new(x, y, 0.0) }, Sprite::new(texture, scale)))
+        .collect();
+    let sprites: Vec<(&GlobalTransform, &Sprite)> = sprites.iter().map(|(transform, sprite)| (transform, sprite)).collect();
+
+    let mut camera_system = Camera_System::new();
+    frame.clear();
+    camera_system.cast_rays(&frame, &player, world, &camera, &textures);
+    camera_system.draw_sprites(&frame, &player, &camera, &sprites, &textures);
+    frame
+}
+
+#[test]
+fn facing_the_pillar_from_the_west() {
+    common::assert_matches_golden("raycast_pillar_west", &render(Position::new(40.0, 84.0, 0.0)));
+}
+
+#[test]
+fn facing_the_pillar_from_the_north() {
+    common::assert_matches_golden("raycast_pillar_north", &render(Position::new(84.0, 40.0, PI / 2.0)));
+}
+
+#[test]
+fn looking_into_a_corner() {
+    common::assert_matches_golden("raycast_corner", &render(Position::new(40.0, 120.0, 3.0 * PI / 4.0)));
+}
+
+#[test]
+fn close_to_a_wall() {
+    common::assert_matches_golden("raycast_wall_close", &render(Position::new(14.0, 60.0, PI)));
+}
+
+#[test]
+fn coloured_floor_under_a_textured_ceiling() {
+    let mut world = WorldData::new(TILES, FRAME_SIZE, FRAME_SIZE);
+    for (y, row) in world.floors.iter_mut().enumerate() {
+        for (x, floor) in row.iter_mut().enumerate() {
+            *floor = if (x + y) % 2 == 0 { Surface::Color(200, 200, 200) } else { Surface::Color(40, 40, 40) };
+        }
+    }
+    for row in world.ceilings.iter_mut() {
+        row.fill(Surface::Texture(2));
+    }
+    common::assert_matches_golden("raycast_floor_and_ceiling", &render_world(&world, Position::new(40.0, 40.0, PI / 4.0)));
+}
+
+// The barrel is partly behind the pillar and the orb in front of it, the second orb is further away than the barrel
+#[test]
+fn sprites_behind_and_in_front_of_the_pillar() {
+    let world = WorldData::new(TILES, FRAME_SIZE, FRAME_SIZE);
+    let sprites = [(60.0, 92.0, 4, 0.4), (108.0, 80.0, 5, 0.6), (140.0, 60.0, 4, 0.4)];
+    common::assert_matches_golden("raycast_sprites", &render_with_sprites(&world, Position::new(30.0, 84.0, 0.0), &sprites));
+}
+
+// Without any textures the walls fall back to flat shaded grey instead of disappearing
+#[test]
+fn walls_without_a_texture_are_drawn_flat() {
+    let frame = FrameBuffer::new(FRAME_SIZE, FRAME_SIZE);
+    let world = WorldData::new(TILES, FRAME_SIZE, FRAME_SIZE);
+    let player = GlobalTransform { position: Position::new(40.0, 84.0, 0.0) };
+    frame.clear();
+    Camera_System::new().cast_rays(&frame, &player, &world, &CameraData::new(), &TextureAtlas::new());
+
+    let [r, g, b, _] = frame.get_pixel(FRAME_SIZE / 2, FRAME_SIZE / 2).expect("The center is inside the frame");
+    assert!(r == g && g == b && r > 100, "expected a grey wall, got {:?}", (r, g, b));
+}